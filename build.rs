@@ -0,0 +1,11 @@
+extern crate gcc;
+
+use std::env;
+
+fn main() {
+    // the lazy binding trampolines want xsave, xsavec and assembler macros, which is a lot nicer in a real .s file than in asm!
+    let target = env::var("TARGET").unwrap();
+    if target.starts_with("x86_64") {
+        gcc::compile_library("libtrampoline.a", &["src/arch/x86_64/trampoline.s"]);
+    }
+}
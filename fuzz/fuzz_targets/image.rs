@@ -0,0 +1,93 @@
+//! Feeds arbitrary bytes through the same checked path the loader uses: header and program header validation, then `SharedObject::from_mapped`
+//! over the segments laid out as they would be mapped, and finally every symbol lookup the linker would do with the result,
+//! and every GOT entry and constructor and destructor array entry it would read (but not call).
+//! Run with `cargo fuzz run image`; seeding the corpus with a few real shared objects from /usr/lib helps a lot.
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate dryad;
+
+use std::mem;
+use std::ptr;
+use dryad::elf::header::Header;
+use dryad::elf::program_header::{self, ProgramHeader};
+use dryad::elf::gnu_hash;
+use dryad::elf::dyn;
+use dryad::image::SharedObject;
+use dryad::loader;
+
+/// Don't bother with anything claiming to span more than this, we'd just spend all our time zeroing memory
+const MAX_IMAGE_SIZE: usize = 16 * 1024 * 1024;
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < mem::size_of::<Header>() {
+        return
+    }
+    let ehdr: Header = unsafe { ptr::read_unaligned(data.as_ptr() as *const Header) };
+    if loader::verify_header(&ehdr, data.len(), false).is_err() {
+        return
+    }
+    let phoff = ehdr.e_phoff as usize;
+    let phdrs: Vec<ProgramHeader> = (0..ehdr.e_phnum as usize).map(|i| {
+        unsafe { ptr::read_unaligned(data[phoff + i * mem::size_of::<ProgramHeader>()..].as_ptr() as *const ProgramHeader) }
+    }).collect();
+    if loader::verify_phdrs(&phdrs, data.len()).is_err() {
+        return
+    }
+
+    // lay the segments out like the loader would, except into a buffer instead of an mmap
+    let loads = || phdrs.iter().filter(|phdr| phdr.p_type == program_header::PT_LOAD);
+    let min_vaddr = loads().map(|phdr| phdr.p_vaddr as usize).min().unwrap();
+    let max_vaddr = loads().map(|phdr| (phdr.p_vaddr + phdr.p_memsz) as usize).max().unwrap();
+    if max_vaddr - min_vaddr > MAX_IMAGE_SIZE {
+        return
+    }
+    // u64s so the tables end up as aligned as they would be in a real mapping
+    let mut image = vec![0u64; (max_vaddr - min_vaddr + 7) / 8];
+    let base = image.as_mut_ptr() as usize;
+    for phdr in loads() {
+        let file = &data[phdr.p_offset as usize..(phdr.p_offset + phdr.p_filesz) as usize];
+        unsafe { ptr::copy_nonoverlapping(file.as_ptr(), (base + phdr.p_vaddr as usize - min_vaddr) as *mut u8, file.len()); }
+    }
+    let load_bias = base.wrapping_sub(min_vaddr);
+
+    let so = match unsafe { SharedObject::from_mapped("fuzz", load_bias, &phdrs) } {
+        Ok (so) => so,
+        Err (_) => return,
+    };
+
+    // if it got this far, nothing the linker does with it should be able to crash
+    let _ = so.name();
+    for lib in &so.libs {
+        let _ = lib.len();
+    }
+    for (idx, sym) in so.symtab.iter().enumerate() {
+        let name = &so.strtab[sym.st_name as usize];
+        let hash = gnu_hash::hash(name);
+        let version = so.requested_version(idx);
+        let _ = so.find(name, hash);
+        let _ = so.find_versioned(name, hash, version.as_ref());
+    }
+
+    // what `link`, `init`, `preinit` and `_dryad_fini` read through; the reads go wild if `check_tables` let a bad address by
+    let word = mem::size_of::<usize>();
+    let read = |addr: usize, size: usize| {
+        for i in 0..size / word {
+            let _ = unsafe { ptr::read_volatile((addr + i * word) as *const usize) };
+        }
+    };
+    if !so.pltgot.is_null() {
+        read(so.pltgot as usize, 3 * word);
+    }
+    read(so.link_info.init_array as usize, so.link_info.init_arraysz as usize);
+    read(so.link_info.fini_array as usize, so.link_info.fini_arraysz as usize);
+    let mut preinit_array = 0;
+    let mut preinit_arraysz = 0;
+    for dyn in so.dynamic {
+        match dyn.d_tag as u64 {
+            dyn::DT_PREINIT_ARRAY => preinit_array = (dyn.d_val as usize).wrapping_add(so.load_bias),
+            dyn::DT_PREINIT_ARRAYSZ => preinit_arraysz = dyn.d_val as usize,
+            _ => ()
+        }
+    }
+    read(preinit_array, preinit_arraysz);
+});
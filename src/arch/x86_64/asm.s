@@ -1,10 +1,3 @@
-	.text
-        .globl _dryad_fini
-        .type _dryad_fini, @function
-_dryad_fini:
-	leaq (%rip), %rax
-        retq
-
 	// this causes segfaults very early on, can't use Mutabah's fix for now...
 .section .data
 _addr_of_dryad_init: .quad dryad_init
@@ -23,74 +16,9 @@ _start:
 //	mov _addr_of_dryad_init(%rip), %rax
 //        callq *%rax
 	callq dryad_init
-	/* // need to add dryad's fini call into rdx... because that's where crt1.o's _start expects it to be
-	mov %rax, %rcx
-	callq _dryad_fini
-	movq %rax, %rdx
-	movq %rcx, %rax
-
-	// crashes here: 
-	__new_exitfn
-
-	*/
-	movq $0, %rdx
+	// crt1's _start expects the dynamic linker's fini in rdx, and hands it to __libc_start_main to atexit
+	leaq _dryad_fini(%rip), %rdx
         jmpq *%rax
         retq
 
-        .text
-        .globl _dryad_resolve_symbol
-        .type _dryad_resolve_symbol, @function
-_dryad_resolve_symbol:
-	push   %rbx
-	mov    %rsp,%rbx
-	and    $0xffffffffffffffe0,%rsp
-	sub    $0x180,%rsp
-	mov    %rax,0x140(%rsp)
-	mov    %rcx,0x148(%rsp)
-	mov    %rdx,0x150(%rsp)
-	mov    %rsi,0x158(%rsp)
-	mov    %rdi,0x160(%rsp)
-	mov    %r8,0x168(%rsp)
-	mov    %r9,0x170(%rsp)
-	vmovdqa %ymm0,(%rsp)
-	vmovdqa %ymm1,0x20(%rsp)
-	vmovdqa %ymm2,0x40(%rsp)
-	vmovdqa %ymm3,0x60(%rsp)
-	vmovdqa %ymm4,0x80(%rsp)
-	vmovdqa %ymm5,0xa0(%rsp)
-	vmovdqa %ymm6,0xc0(%rsp)
-	vmovdqa %ymm7,0xe0(%rsp)
-//	bndmov %bnd0,0x100(%rsp)
-//	bndmov %bnd1,0x110(%rsp)
-//	bndmov %bnd2,0x120(%rsp)
-//	bndmov %bnd3,0x130(%rsp)
-	mov    0x10(%rbx),%rsi
-	mov    0x8(%rbx),%rdi
-//	mov _addr_of_dryad_resolve_symbol(%rip), %rax
-//	callq  *(%rax)
-	callq dryad_resolve_symbol
-	mov    %rax,%r11
-//	bndmov 0x130(%rsp),%bnd3
-//	bndmov 0x120(%rsp),%bnd2
-//	bndmov 0x110(%rsp),%bnd1
-//	bndmov 0x100(%rsp),%bnd0
-	mov    0x170(%rsp),%r9
-	mov    0x168(%rsp),%r8
-	mov    0x160(%rsp),%rdi
-	mov    0x158(%rsp),%rsi
-	mov    0x150(%rsp),%rdx
-	mov    0x148(%rsp),%rcx
-	mov    0x140(%rsp),%rax
-	vmovdqa (%rsp),%ymm0
-	vmovdqa 0x20(%rsp),%ymm1
-	vmovdqa 0x40(%rsp),%ymm2
-	vmovdqa 0x60(%rsp),%ymm3
-	vmovdqa 0x80(%rsp),%ymm4
-	vmovdqa 0xa0(%rsp),%ymm5
-	vmovdqa 0xc0(%rsp),%ymm6
-	vmovdqa 0xe0(%rsp),%ymm7
-	mov    %rbx,%rsp
-	mov    (%rsp),%rbx
-	add    $0x18,%rsp
-	jmpq *%r11
-	nopl   0x0(%rax)
+// the lazy binding trampolines live in trampoline.s
@@ -0,0 +1,118 @@
+// Lazy binding trampolines, i.e., what GOT[2] points at.
+//
+// PLT0 has pushed GOT[1] (our `Rendezvous`), and the PLT entry pushed the relocation index before that, so on entry:
+//   0x0(%rsp)  = rendezvous
+//   0x8(%rsp)  = rela index
+//   0x10(%rsp) = return address into whoever called through the PLT
+//
+// We're in the middle of someone else's function call, so we must preserve every register they may have passed arguments in:
+// the integer argument registers, %rax (the vector register count for varargs), and the _entire_ vector state.
+// On an AVX-512 machine that's zmm0-31 and the k-registers, on an MPX machine the bnd registers, and on a machine without AVX
+// we can't so much as mention a ymm register without faulting; hence three variants, one of which `runtime::select_trampoline`
+// picks via cpuid at startup, and `prepare_got` installs in every GOT[2].
+//
+// The frame is: the integer registers in the first 0x40 bytes, then the fxsave/xsave area, everything 64-byte aligned since xsave requires it.
+
+	.set REGISTER_SAVE_AREA, 0x40
+	.set STATE_SAVE_OFFSET, REGISTER_SAVE_AREA
+	.set FXSAVE_SIZE, 512
+
+	.macro TRAMPOLINE_ENTER
+	push   %rbx
+	mov    %rsp,%rbx
+	and    $~63,%rsp
+	.endm
+
+	.macro SAVE_GPRS
+	mov    %rax,0x00(%rsp)
+	mov    %rcx,0x08(%rsp)
+	mov    %rdx,0x10(%rsp)
+	mov    %rsi,0x18(%rsp)
+	mov    %rdi,0x20(%rsp)
+	mov    %r8,0x28(%rsp)
+	mov    %r9,0x30(%rsp)
+	.endm
+
+	.macro CALL_RESOLVER
+	mov    0x10(%rbx),%rsi
+	mov    0x8(%rbx),%rdi
+	callq  dryad_resolve_symbol
+	mov    %rax,%r11
+	.endm
+
+	.macro RESTORE_GPRS_AND_JUMP
+	mov    0x30(%rsp),%r9
+	mov    0x28(%rsp),%r8
+	mov    0x20(%rsp),%rdi
+	mov    0x18(%rsp),%rsi
+	mov    0x10(%rsp),%rdx
+	mov    0x08(%rsp),%rcx
+	mov    0x00(%rsp),%rax
+	mov    %rbx,%rsp
+	mov    (%rsp),%rbx
+	// pop the rendezvous and rela index the PLT pushed
+	add    $0x18,%rsp
+	jmpq   *%r11
+	.endm
+
+// fxsave: x87, mxcsr and xmm0-15; every x86_64 has it, and on a machine without xsave that's all the vector state there is
+	.text
+	.globl _dryad_resolve_symbol_fxsave
+	.hidden _dryad_resolve_symbol_fxsave
+	.type _dryad_resolve_symbol_fxsave, @function
+_dryad_resolve_symbol_fxsave:
+	TRAMPOLINE_ENTER
+	sub    $(STATE_SAVE_OFFSET + FXSAVE_SIZE),%rsp
+	SAVE_GPRS
+	fxsave STATE_SAVE_OFFSET(%rsp)
+	CALL_RESOLVER
+	fxrstor STATE_SAVE_OFFSET(%rsp)
+	RESTORE_GPRS_AND_JUMP
+	.size _dryad_resolve_symbol_fxsave, .-_dryad_resolve_symbol_fxsave
+
+// xsave: every state component the OS enabled in XCR0 (edx:eax = -1 requests them all), which covers ymm, zmm, k and bnd registers
+// the frame size depends on what's enabled, so `select_trampoline` computes it from cpuid into `_dryad_xsave_state_size`
+	.text
+	.globl _dryad_resolve_symbol_xsave
+	.hidden _dryad_resolve_symbol_xsave
+	.type _dryad_resolve_symbol_xsave, @function
+_dryad_resolve_symbol_xsave:
+	TRAMPOLINE_ENTER
+	sub    _dryad_xsave_state_size(%rip),%rsp
+	SAVE_GPRS
+	mov    $-1,%eax
+	mov    $-1,%edx
+	// xsave only writes XSTATE_BV in the header, but xrstor faults if the rest of it isn't zero
+	movq   $0,(STATE_SAVE_OFFSET + FXSAVE_SIZE + 0x08)(%rsp)
+	movq   $0,(STATE_SAVE_OFFSET + FXSAVE_SIZE + 0x10)(%rsp)
+	movq   $0,(STATE_SAVE_OFFSET + FXSAVE_SIZE + 0x18)(%rsp)
+	movq   $0,(STATE_SAVE_OFFSET + FXSAVE_SIZE + 0x20)(%rsp)
+	movq   $0,(STATE_SAVE_OFFSET + FXSAVE_SIZE + 0x28)(%rsp)
+	movq   $0,(STATE_SAVE_OFFSET + FXSAVE_SIZE + 0x30)(%rsp)
+	movq   $0,(STATE_SAVE_OFFSET + FXSAVE_SIZE + 0x38)(%rsp)
+	xsave  STATE_SAVE_OFFSET(%rsp)
+	CALL_RESOLVER
+	mov    $-1,%eax
+	mov    $-1,%edx
+	xrstor STATE_SAVE_OFFSET(%rsp)
+	RESTORE_GPRS_AND_JUMP
+	.size _dryad_resolve_symbol_xsave, .-_dryad_resolve_symbol_xsave
+
+// xsavec: as above, but compacted, and it skips components still in their init state, so it's both smaller and faster when available
+	.text
+	.globl _dryad_resolve_symbol_xsavec
+	.hidden _dryad_resolve_symbol_xsavec
+	.type _dryad_resolve_symbol_xsavec, @function
+_dryad_resolve_symbol_xsavec:
+	TRAMPOLINE_ENTER
+	sub    _dryad_xsave_state_size(%rip),%rsp
+	SAVE_GPRS
+	mov    $-1,%eax
+	mov    $-1,%edx
+	xsavec STATE_SAVE_OFFSET(%rsp)
+	CALL_RESOLVER
+	mov    $-1,%eax
+	mov    $-1,%edx
+	xrstor STATE_SAVE_OFFSET(%rsp)
+	RESTORE_GPRS_AND_JUMP
+	.size _dryad_resolve_symbol_xsavec, .-_dryad_resolve_symbol_xsavec
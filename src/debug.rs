@@ -0,0 +1,136 @@
+/// `LD_DEBUG`, i.e., what the `dbg!` and `dbgc!` macros print, and where.
+///
+/// Like glibc, `LD_DEBUG` is a list of categories separated by spaces, commas or colons, e.g., `LD_DEBUG=libs,bindings`, and `LD_DEBUG=help` lists them.
+/// Everything goes to stderr, or when `LD_DEBUG_OUTPUT` is set (and we're not running setuid), to `$LD_DEBUG_OUTPUT.<pid>`,
+/// and every line starts with our pid, so the output of a program that forks (or `system`s) stays readable.
+///
+/// The state is global (and written once, right after we relocate ourselves) because the macros are used everywhere, including places with no linker in sight, like tls.
+use std::fmt;
+use std::ffi::CString;
+use libc;
+
+use utils;
+
+pub const LIBS: u32       = 1 << 0;
+pub const RELOC: u32      = 1 << 1;
+pub const FILES: u32      = 1 << 2;
+pub const SYMBOLS: u32    = 1 << 3;
+pub const BINDINGS: u32   = 1 << 4;
+pub const VERSIONS: u32   = 1 << 5;
+pub const SCOPES: u32     = 1 << 6;
+pub const TLS: u32        = 1 << 7;
+pub const STATISTICS: u32 = 1 << 8;
+pub const UNUSED: u32     = 1 << 9;
+/// Like glibc, `all` is everything except `statistics` and `unused`, which change what we do rather than just narrate it
+pub const ALL: u32 = LIBS | RELOC | FILES | SYMBOLS | BINDINGS | VERSIONS | SCOPES | TLS;
+
+const OPTIONS: [(&'static str, u32, &'static str); 11] = [
+    ("libs", LIBS, "display library search paths"),
+    ("reloc", RELOC, "display relocation processing"),
+    ("files", FILES, "display progress for input file"),
+    ("symbols", SYMBOLS, "display symbol table processing"),
+    ("bindings", BINDINGS, "display information about symbol binding"),
+    ("versions", VERSIONS, "display version dependencies"),
+    ("scopes", SCOPES, "display scope information"),
+    ("tls", TLS, "display TLS structures processing"),
+    ("all", ALL, "all previous options combined"),
+    ("statistics", STATISTICS, "display relocation statistics"),
+    ("unused", UNUSED, "determine unused DSOs"),
+];
+
+static mut MASK: u32 = 0;
+static mut FD: libc::c_int = 2;
+static mut COLOR: bool = false;
+
+/// What `LD_DEBUG=help` prints
+pub fn help () -> String {
+    let mut help = "Valid options for the LD_DEBUG environment variable are:\n\n".to_string();
+    for &(name, _, description) in OPTIONS.iter() {
+        help.push_str(&format!("  {:<11} {}\n", name, description));
+    }
+    help.push_str(&format!("  {:<11} {}\n", "help", "display this help message and exit"));
+    help.push_str("\nTo direct the debugging output into a file instead of standard error\na filename can be specified using the LD_DEBUG_OUTPUT environment variable.\n");
+    help
+}
+
+/// Parses an `LD_DEBUG` value into the categories it asks for, the options we didn't recognize, and whether it asked for help
+pub fn parse (spec: &str) -> (u32, Vec<&str>, bool) {
+    let mut mask = 0;
+    let mut unknown = Vec::new();
+    let mut help = false;
+    for option in spec.split(|c| c == ' ' || c == ',' || c == ':').filter(|option| *option != "") {
+        if option == "help" {
+            help = true;
+        } else if let Some(&(_, bits, _)) = OPTIONS.iter().find(|&&(name, _, _)| name == option) {
+            mask |= bits;
+        } else {
+            unknown.push(option);
+        }
+    }
+    (mask, unknown, help)
+}
+
+#[test]
+fn parse_t () {
+    assert_eq!(parse("libs"), (LIBS, vec![], false));
+    assert_eq!(parse("libs,bindings:reloc  tls"), (LIBS | BINDINGS | RELOC | TLS, vec![], false));
+    assert_eq!(parse("all"), (ALL, vec![], false));
+    assert_eq!(parse("all,unused"), (ALL | UNUSED, vec![], false));
+    assert_eq!(parse("files,bogus,help"), (FILES, vec!["bogus"], true));
+    assert_eq!(parse(""), (0, vec![], false));
+}
+
+/// Turns on the categories in `spec`, and opens `$output.<pid>` for them if there's an `output` (glibc ignores `LD_DEBUG_OUTPUT` when `secure`, so the caller should too).
+/// `LD_DEBUG=help` prints the help and exits, like ld.so.
+/// Both it and the warnings about unknown options go to stderr, like the rest of the debug output, since they're not the program's to print
+/// (and `LD_DEBUG_OUTPUT` isn't open yet, nor worth opening for them).
+/// **NB**: only call this once we've relocated ourselves, we allocate
+pub fn init (spec: &str, output: Option<&str>) {
+    let (mask, unknown, help) = parse(spec);
+    for option in unknown {
+        utils::write_err(&format!("dryad: warning: debug option `{}' unknown; try LD_DEBUG=help\n", option));
+    }
+    if help {
+        utils::write_err(&self::help());
+        utils::_exit(0);
+    }
+    unsafe {
+        MASK = mask;
+        if mask != 0 {
+            if let Some(output) = output {
+                if let Ok(path) = CString::new(format!("{}.{}", output, libc::getpid())) {
+                    let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CREAT | libc::O_APPEND, 0o666);
+                    // like glibc, if we can't open it, we stick with stderr
+                    if fd >= 0 {
+                        FD = fd;
+                    }
+                }
+            }
+        }
+        COLOR = libc::isatty(FD) == 1;
+    }
+}
+
+/// Whether any of the categories in `category` are on
+#[inline(always)]
+pub fn enabled (category: u32) -> bool {
+    unsafe { MASK & category != 0 }
+}
+
+/// Whether the output is a terminal, and so gets colored prefixes
+#[inline(always)]
+pub fn color () -> bool {
+    unsafe { COLOR }
+}
+
+/// Writes `args` to the debug output in one go, every line prefixed with our pid and `prefix`
+pub fn print (prefix: &str, args: fmt::Arguments) {
+    // asked every time, not cached, since after a fork the child is still writing here (and to the same file), and its lines had better say so
+    let pid = unsafe { libc::getpid() };
+    let msg = fmt::format(args);
+    let mut out = String::with_capacity(msg.len() + 16);
+    for line in msg.lines() {
+        out.push_str(&format!("{:>5}:\t{} {}\n", pid, prefix, line));
+    }
+    unsafe { libc::write(FD, out.as_ptr() as *const libc::c_void, out.len()); }
+}
@@ -0,0 +1,260 @@
+/// dryad's own dlopen entry points, for objects which don't live at a path: a memfd or any fd at some page aligned offset, e.g., a library stored uncompressed inside
+/// some archive, or a plain buffer, e.g., an object a sandboxed host received over IPC and never wants to touch the disk with.
+///
+/// Handles are opaque; they're the object's index in the link map, plus one so that no handle is ever null.
+use std::ffi::{CStr, CString};
+use std::mem;
+use std::ptr;
+use std::slice;
+use libc;
+
+use elf::program_header::ProgramHeader;
+use linker::{self, Linker, RelroSharing};
+use loader::{self, Placement, Source};
+use lock;
+use utils::page;
+
+pub const RTLD_LAZY: libc::c_int = 0x1;
+pub const RTLD_NOW: libc::c_int = 0x2;
+/// The default: the object and its dependencies are only visible to each other, and to `dryad_dlsym`
+pub const RTLD_LOCAL: libc::c_int = 0;
+/// Add the object and its dependencies to the global scope, for everything loaded afterwards (and lazy binding in everything already loaded) to find
+pub const RTLD_GLOBAL: libc::c_int = 0x100;
+
+// `dryad_dlextinfo` flags; the same values as bionic's `ANDROID_DLEXT_*`, so code written against `android_dlopen_ext` ports over unchanged
+/// Load into `reserved_addr`, failing if the object doesn't fit in `reserved_size`
+pub const DLEXT_RESERVED_ADDRESS: u64 = 0x1;
+/// Like `DLEXT_RESERVED_ADDRESS`, but load anywhere if the object doesn't fit
+pub const DLEXT_RESERVED_ADDRESS_HINT: u64 = 0x2;
+/// Write the relocated RELRO to `relro_fd`
+pub const DLEXT_WRITE_RELRO: u64 = 0x4;
+/// Share the pages of a RELRO previously written to `relro_fd`
+pub const DLEXT_USE_RELRO: u64 = 0x8;
+/// Load from `library_fd` instead of opening the file; the filename is then just a name
+pub const DLEXT_USE_LIBRARY_FD: u64 = 0x10;
+/// With `DLEXT_USE_LIBRARY_FD`, the object starts at `library_fd_offset`, e.g., an uncompressed library inside an apk
+pub const DLEXT_USE_LIBRARY_FD_OFFSET: u64 = 0x20;
+const DLEXT_SUPPORTED: u64 = DLEXT_RESERVED_ADDRESS | DLEXT_RESERVED_ADDRESS_HINT | DLEXT_WRITE_RELRO | DLEXT_USE_RELRO | DLEXT_USE_LIBRARY_FD | DLEXT_USE_LIBRARY_FD_OFFSET;
+
+/// Laid out exactly like bionic's `android_dlextinfo`; we don't do namespaces, so `library_namespace` must be null
+#[repr(C)]
+pub struct DlExtInfo {
+    pub flags: u64,
+    pub reserved_addr: *mut libc::c_void,
+    pub reserved_size: libc::size_t,
+    pub relro_fd: libc::c_int,
+    pub library_fd: libc::c_int,
+    pub library_fd_offset: i64,
+    pub library_namespace: *const libc::c_void,
+}
+
+/// The last error, waiting for someone to call `dryad_dlerror`
+static mut ERROR: Option<CString> = None;
+/// The error `dryad_dlerror` last returned, kept alive until the next call like glibc does
+static mut RETURNED_ERROR: Option<CString> = None;
+
+unsafe fn set_error (msg: &str) {
+    ERROR = CString::new(msg.trim_left_matches("Error: ")).ok();
+}
+
+unsafe fn linker<'a> () -> Option<&'a mut Linker<'static>> {
+    if linker::LINKER.is_null() {
+        set_error("dlopen called before the program was linked");
+        None
+    } else {
+        Some (&mut *(linker::LINKER as *mut Linker<'static>))
+    }
+}
+
+/// `verified` is `None` unless we already verified `source` while searching for it, in which case there's no need to read its headers twice
+unsafe fn dlopen (name: &str, source: &Source, verified: Option<loader::Verified>, flags: libc::c_int, placement: Placement, relro: RelroSharing) -> *const libc::c_void {
+    let verified = match verified {
+        Some (verified) => verified,
+        None => match loader::verify(source, false) {
+            Ok (verified) => verified,
+            Err (e) => {
+                set_error(&format!("{}: {}", name, e.trim_left_matches("Error: ")));
+                return ptr::null()
+            }
+        }
+    };
+    let _guard = lock::LINK_LOCK.lock();
+    let linker = match linker() { Some (linker) => linker, None => return ptr::null() };
+    match linker.dlopen(name, source, verified, flags, placement, relro) {
+        Ok (idx) => (idx + 1) as *const libc::c_void,
+        Err (e) => {
+            set_error(&format!("{}: {}", name, e.trim_left_matches("Error: ")));
+            ptr::null()
+        }
+    }
+}
+
+/// Loads the shared object starting at `offset` in the file open at `fd`, which can be a memfd; `offset` must be a multiple of the page size.
+/// `fd` is only borrowed, so the caller can close it as soon as this returns.  Returns null on failure; see `dryad_dlerror`.
+#[no_mangle]
+pub unsafe extern fn dryad_dlopen_fd (fd: libc::c_int, offset: libc::off_t, flags: libc::c_int) -> *const libc::c_void {
+    if offset < 0 || page::page_offset(offset as usize) != 0 {
+        set_error(&format!("cannot dlopen fd {}: offset {:#x} is not page aligned", fd, offset));
+        return ptr::null()
+    }
+    // name it like the kernel does in /proc/self/maps, so dedup, debug output and gdb have something to go on, e.g., "/memfd:plugin (deleted)"
+    let name = match ::std::fs::read_link(format!("/proc/self/fd/{}", fd)) {
+        Ok (path) => format!("{}@{:#x}", path.display(), offset),
+        Err (_) => format!("fd:{}@{:#x}", fd, offset),
+    };
+    dlopen(&name, &Source::Fd { fd: fd, offset: offset as usize }, None, flags, Placement::Anywhere, RelroSharing::Private)
+}
+
+/// Loads the shared object in the `len` bytes at `ptr`, calling it `name`; the bytes are copied, so the caller can free them as soon as this returns.
+/// Returns null on failure; see `dryad_dlerror`.
+#[no_mangle]
+pub unsafe extern fn dryad_dlopen_mem (ptr: *const u8, len: libc::size_t, name: *const libc::c_char, flags: libc::c_int) -> *const libc::c_void {
+    if ptr.is_null() || name.is_null() {
+        set_error("dryad_dlopen_mem: null buffer or name");
+        return ptr::null()
+    }
+    let name = CStr::from_ptr(name).to_string_lossy().into_owned();
+    dlopen(&name, &Source::Memory (slice::from_raw_parts(ptr, len as usize)), None, flags, Placement::Anywhere, RelroSharing::Private)
+}
+
+/// `android_dlopen_ext` for dryad: loads `filename` (or whatever `extinfo` says to load instead) with the extras `extinfo` asks for; a null `extinfo` is just a dlopen.
+/// Returns null on failure; see `dryad_dlerror`.
+#[no_mangle]
+pub unsafe extern fn dryad_dlopen_ext (filename: *const libc::c_char, flags: libc::c_int, extinfo: *const DlExtInfo) -> *const libc::c_void {
+    if filename.is_null() {
+        set_error("dryad_dlopen_ext: null filename");
+        return ptr::null()
+    }
+    let name = CStr::from_ptr(filename).to_string_lossy().into_owned();
+    let default = DlExtInfo { flags: 0, reserved_addr: ptr::null_mut(), reserved_size: 0, relro_fd: -1, library_fd: -1, library_fd_offset: 0, library_namespace: ptr::null() };
+    let info = if extinfo.is_null() { &default } else { &*extinfo };
+
+    if info.flags & !DLEXT_SUPPORTED != 0 || !info.library_namespace.is_null() {
+        set_error(&format!("{}: unsupported dlextinfo flags {:#x}", name, info.flags & !DLEXT_SUPPORTED));
+        return ptr::null()
+    }
+    if info.flags & DLEXT_WRITE_RELRO != 0 && info.flags & DLEXT_USE_RELRO != 0 {
+        set_error(&format!("{}: can't both write and use a relro file", name));
+        return ptr::null()
+    }
+    if info.flags & DLEXT_USE_LIBRARY_FD_OFFSET != 0 && info.flags & DLEXT_USE_LIBRARY_FD == 0 {
+        set_error(&format!("{}: DLEXT_USE_LIBRARY_FD_OFFSET without DLEXT_USE_LIBRARY_FD", name));
+        return ptr::null()
+    }
+
+    let placement = if info.flags & (DLEXT_RESERVED_ADDRESS | DLEXT_RESERVED_ADDRESS_HINT) != 0 {
+        Placement::Reserved { addr: info.reserved_addr as usize, size: info.reserved_size as usize, hint: info.flags & DLEXT_RESERVED_ADDRESS == 0 }
+    } else {
+        Placement::Anywhere
+    };
+    let relro = if info.flags & DLEXT_WRITE_RELRO != 0 {
+        RelroSharing::Write (info.relro_fd)
+    } else if info.flags & DLEXT_USE_RELRO != 0 {
+        RelroSharing::Use (info.relro_fd)
+    } else {
+        RelroSharing::Private
+    };
+
+    if info.flags & DLEXT_USE_LIBRARY_FD != 0 {
+        let offset = if info.flags & DLEXT_USE_LIBRARY_FD_OFFSET != 0 { info.library_fd_offset } else { 0 };
+        if offset < 0 || page::page_offset(offset as usize) != 0 {
+            set_error(&format!("{}: library fd offset {:#x} is not page aligned", name, offset));
+            return ptr::null()
+        }
+        dlopen(&name, &Source::Fd { fd: info.library_fd, offset: offset as usize }, None, flags, placement, relro)
+    } else {
+        // like dlopen, a name with a slash is a path, anything else we search for
+        let _guard = lock::LINK_LOCK.lock();
+        let opened = match linker() {
+            Some (linker) => linker.open(&name).map(|(path, file, verified)| (path.to_string_lossy().into_owned(), file, verified)),
+            None => return ptr::null(),
+        };
+        match opened {
+            Ok ((path, file, verified)) => dlopen(&path, &Source::from_file(&file), Some (verified), flags, placement, relro),
+            Err (e) => {
+                set_error(&format!("{}: {}", name, e.trim_left_matches("Error: ")));
+                ptr::null()
+            }
+        }
+    }
+}
+
+/// The address of `symbol` in the object `handle` refers to, or failing that, in its dependencies, breadth first; null if none of them have it
+#[no_mangle]
+pub unsafe extern fn dryad_dlsym (handle: *const libc::c_void, symbol: *const libc::c_char) -> *const libc::c_void {
+    if symbol.is_null() {
+        set_error("dryad_dlsym: null symbol name");
+        return ptr::null()
+    }
+    let _guard = lock::LINK_LOCK.lock();
+    let linker = match linker() { Some (linker) => linker, None => return ptr::null() };
+    let name = CStr::from_ptr(symbol).to_string_lossy();
+    match linker.dlsym(handle as usize, &name) {
+        Ok (addr) => addr as *const libc::c_void,
+        Err (e) => {
+            set_error(&e);
+            ptr::null()
+        }
+    }
+}
+
+/// What `dryad_dl_iterate_phdr` hands its callback for each object, laid out like `struct dl_phdr_info`
+#[repr(C)]
+pub struct DlPhdrInfo {
+    pub dlpi_addr: usize,
+    pub dlpi_name: *const libc::c_char,
+    pub dlpi_phdr: *const ProgramHeader,
+    pub dlpi_phnum: u16,
+    /// How many objects have ever been loaded, and unloaded; we never unload, so the latter is always 0
+    pub dlpi_adds: u64,
+    pub dlpi_subs: u64,
+    pub dlpi_tls_modid: libc::size_t,
+    /// Always null, i.e., the calling thread hasn't allocated the block, even when it has
+    pub dlpi_tls_data: *mut libc::c_void,
+}
+
+pub type DlIteratePhdrCallback = extern fn (info: *mut DlPhdrInfo, size: libc::size_t, data: *mut libc::c_void) -> libc::c_int;
+
+/// `dl_iterate_phdr` for dryad: calls `callback` with every object in the link map, in load order, the executable (with an empty name, like glibc) first,
+/// until it returns non-zero, which we then return.  The link lock is held throughout, so callbacks see a consistent link map, and can still dlopen.
+#[no_mangle]
+pub unsafe extern fn dryad_dl_iterate_phdr (callback: Option<DlIteratePhdrCallback>, data: *mut libc::c_void) -> libc::c_int {
+    let callback = match callback { Some (callback) => callback, None => return 0 };
+    let _guard = lock::LINK_LOCK.lock();
+    let count = match linker() { Some (linker) => linker.link_map().len(), None => return 0 };
+    // a callback which dlopens grows the link map, so we look it up afresh each time, and only visit what was there when we started
+    for i in 0..count {
+        let (mut info, _name) = {
+            let link_map = match linker() { Some (linker) => linker.link_map(), None => return 0 };
+            let so = &link_map[i];
+            let name = CString::new(if i == 0 { "" } else { so.path() }).unwrap_or_default();
+            let info = DlPhdrInfo {
+                dlpi_addr: so.load_bias,
+                dlpi_name: name.as_ptr(),
+                dlpi_phdr: so.phdrs.as_ptr(),
+                dlpi_phnum: so.phdrs.len() as u16,
+                dlpi_adds: link_map.len() as u64,
+                dlpi_subs: 0,
+                dlpi_tls_modid: so.tls.map(|tls| tls.modid as libc::size_t).unwrap_or(0),
+                dlpi_tls_data: ptr::null_mut(),
+            };
+            // the name has to outlive the callback
+            (info, name)
+        };
+        let ret = callback(&mut info, mem::size_of::<DlPhdrInfo>(), data);
+        if ret != 0 {
+            return ret
+        }
+    }
+    0
+}
+
+/// The last error from one of the above, or null if there wasn't one since the last call
+#[no_mangle]
+pub unsafe extern fn dryad_dlerror () -> *const libc::c_char {
+    RETURNED_ERROR = ERROR.take();
+    match RETURNED_ERROR {
+        Some (ref msg) => msg.as_ptr(),
+        None => ptr::null(),
+    }
+}
@@ -0,0 +1,184 @@
+/// `LD_DRYAD_DUMP=path` (or `--dump path` when we're run directly): once the program is linked, and before it runs, write out everything we decided,
+/// for auditing: every object with its load path, load bias, mappings and TLS module, the dependency graph, the search scopes, and every symbol binding.
+///
+/// It's JSON, unless `path` ends in `.dot` or `.gv`, in which case it's just the dependency graph, for graphviz, with the edges to `DT_NEEDED`s which
+/// satisfied none of the requester's relocations dashed (see `LD_DEBUG=unused`).
+///
+/// It's what was bound when we wrote it, i.e., right before the constructors run, and dumping doesn't change that: every `GLOB_DAT` and friends,
+/// but the `JUMP_SLOT`s lazy binding would get around to later aren't in it (and the edges to whoever only provides those look unused),
+/// unless the program binds eagerly anyway, e.g., with `LD_BIND_NOW=1`, which is how to see them all.
+/// Addresses are strings of hex, since JSON numbers don't survive 64 bits in most places that read them.
+use std::fs::File;
+use std::io::Write;
+
+use elf::program_header;
+use image::SharedObject;
+use scope::{Bound, Scopes};
+
+/// `s` as the inside of a JSON string, which does for a DOT one too
+pub fn escape (s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[test]
+fn escape_t () {
+    assert_eq!(escape("libc.so.6"), "libc.so.6");
+    assert_eq!(escape("/tmp/\"odd\"\\dir/lib\n.so"), "/tmp/\\\"odd\\\"\\\\dir/lib\\n.so");
+    assert_eq!(escape("\x01"), "\\u0001");
+}
+
+/// What we dump of an object, taken from its `SharedObject` (see `Node::new`), or made up, for testing
+pub struct Node<'a> {
+    pub name: &'a str,
+    pub path: &'a str,
+    pub load_bias: usize,
+    pub map: (usize, usize),
+    /// The biased `[start, end)` of each `PT_LOAD`, and its "rwx" flags
+    pub segments: Vec<(usize, usize, String)>,
+    /// Module id and offset
+    pub tls: Option<(u32, isize)>,
+}
+
+impl<'a> Node<'a> {
+    pub fn new (so: &'a SharedObject) -> Node<'a> {
+        let segments = so.phdrs.iter().filter(|phdr| phdr.p_type == program_header::PT_LOAD).map(|phdr| {
+            let start = (phdr.p_vaddr as usize).wrapping_add(so.load_bias);
+            let flags = format!("{}{}{}",
+                                if phdr.p_flags & program_header::PF_R != 0 { "r" } else { "-" },
+                                if phdr.p_flags & program_header::PF_W != 0 { "w" } else { "-" },
+                                if phdr.p_flags & program_header::PF_X != 0 { "x" } else { "-" });
+            (start, start + phdr.p_memsz as usize, flags)
+        }).collect();
+        Node {
+            name: so.name(),
+            path: so.path(),
+            load_bias: so.load_bias,
+            map: (so.map_begin, so.map_end),
+            segments: segments,
+            tls: so.tls.as_ref().map(|tls| (tls.modid, tls.offset)),
+        }
+    }
+}
+
+fn indices (list: &[usize]) -> String {
+    list.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+fn object (idx: usize, node: &Node, needed: &[usize], local: &[usize]) -> String {
+    let segments: Vec<String> = node.segments.iter().map(|&(start, end, ref flags)| {
+        format!("{{\"start\": \"0x{:x}\", \"end\": \"0x{:x}\", \"flags\": \"{}\"}}", start, end, flags)
+    }).collect();
+    let tls = match node.tls {
+        Some ((module, offset)) => format!("{{\"module\": {}, \"offset\": {}}}", module, offset),
+        None => "null".to_string(),
+    };
+    format!("    {{\"index\": {}, \"name\": \"{}\", \"path\": \"{}\", \"load_bias\": \"0x{:x}\", \"map\": [\"0x{:x}\", \"0x{:x}\"], \"segments\": [{}], \"tls\": {}, \"needed\": [{}], \"local_scope\": [{}]}}",
+            idx, escape(node.name), escape(node.path), node.load_bias, node.map.0, node.map.1, segments.join(", "), tls, indices(needed), indices(local))
+}
+
+/// Everything, as JSON; `deps` and `bound` are by link map index, like `scopes.local`
+pub fn json (nodes: &[Node], deps: &[Vec<usize>], scopes: &Scopes, bound: &[Bound]) -> String {
+    let objects: Vec<String> = nodes.iter().enumerate().map(|(i, node)| {
+        let local = scopes.local.get(i).map(|local| &local[..]).unwrap_or(&[]);
+        object(i, node, &deps[i], local)
+    }).collect();
+    let mut bindings = Vec::new();
+    for (requester, bound) in bound.iter().enumerate() {
+        for binding in &bound.bindings {
+            let version = match binding.version {
+                Some (ref version) => format!("\"{}\"", escape(version)),
+                None => "null".to_string(),
+            };
+            bindings.push(format!("    {{\"requester\": {}, \"symbol\": \"{}\", \"version\": {}, \"provider\": {}, \"address\": \"0x{:x}\"}}",
+                                  requester, escape(&binding.symbol), version, binding.provider, binding.addr));
+        }
+    }
+    format!("{{\n  \"objects\": [\n{}\n  ],\n  \"global_scope\": [{}],\n  \"bindings\": [\n{}\n  ]\n}}\n",
+            objects.join(",\n"), indices(&scopes.global), bindings.join(",\n"))
+}
+
+/// The dependency graph, for graphviz
+pub fn dot (nodes: &[Node], deps: &[Vec<usize>], bound: &[Bound]) -> String {
+    let mut dot = "digraph dryad {\n  node [shape=box];\n".to_string();
+    for (i, node) in nodes.iter().enumerate() {
+        dot.push_str(&format!("  n{} [label=\"{}\", tooltip=\"{} @ 0x{:x}\"];\n", i, escape(node.name), escape(node.path), node.load_bias));
+    }
+    for (i, needed) in deps.iter().enumerate() {
+        let used = bound.get(i).map(|bound| &bound.providers[..]).unwrap_or(&[]);
+        for dep in needed {
+            let style = if used.contains(dep) { "" } else { " [style=dashed]" };
+            dot.push_str(&format!("  n{} -> n{}{};\n", i, dep, style));
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+/// Writes the dump to `path`, as DOT if it ends in `.dot` or `.gv`, and JSON otherwise
+pub fn write (path: &str, link_map: &[SharedObject], deps: &[Vec<usize>], scopes: &Scopes, bound: &[Bound]) -> Result<(), String> {
+    let nodes: Vec<Node> = link_map.iter().map(Node::new).collect();
+    let dump = if path.ends_with(".dot") || path.ends_with(".gv") {
+        dot(&nodes, deps, bound)
+    } else {
+        json(&nodes, deps, scopes, bound)
+    };
+    let mut file = File::create(path).map_err(|e| format!("Error: cannot create {}: {}", path, e))?;
+    file.write_all(dump.as_bytes()).map_err(|e| format!("Error: cannot write {}: {}", path, e))
+}
+
+#[cfg(test)]
+fn graph () -> (Vec<Node<'static>>, Vec<Vec<usize>>, Scopes, Vec<Bound>) {
+    use scope::Binding;
+    // a program needing liba, which binds to it, and libb, which it doesn't
+    let node = |name, path, load_bias| Node { name: name, path: path, load_bias: load_bias, map: (load_bias, load_bias + 0x2000), segments: vec![(load_bias, load_bias + 0x1000, "r-x".to_string())], tls: None };
+    let mut nodes = vec![node("", "/bin/prog", 0x1000), node("liba.so", "/lib/liba.so", 0x10000), node("libb.so", "/lib/libb.so", 0x20000)];
+    nodes[1].tls = Some ((1, 16));
+    let deps = vec![vec![1, 2], vec![], vec![]];
+    let scopes = Scopes { global: vec![0, 1, 2], local: vec![vec![0, 1, 2], vec![1], vec![2]] };
+    let mut bound: Vec<Bound> = (0..3).map(|_| Bound::default()).collect();
+    bound[0].providers.push(1);
+    bound[0].bindings.push(Binding { symbol: "a".to_string(), version: Some ("V1".to_string()), provider: 1, addr: 0x10100 });
+    (nodes, deps, scopes, bound)
+}
+
+#[test]
+fn json_t () {
+    let (nodes, deps, scopes, bound) = graph();
+    assert_eq!(json(&nodes, &deps, &scopes, &bound), r#"{
+  "objects": [
+    {"index": 0, "name": "", "path": "/bin/prog", "load_bias": "0x1000", "map": ["0x1000", "0x3000"], "segments": [{"start": "0x1000", "end": "0x2000", "flags": "r-x"}], "tls": null, "needed": [1, 2], "local_scope": [0, 1, 2]},
+    {"index": 1, "name": "liba.so", "path": "/lib/liba.so", "load_bias": "0x10000", "map": ["0x10000", "0x12000"], "segments": [{"start": "0x10000", "end": "0x11000", "flags": "r-x"}], "tls": {"module": 1, "offset": 16}, "needed": [], "local_scope": [1]},
+    {"index": 2, "name": "libb.so", "path": "/lib/libb.so", "load_bias": "0x20000", "map": ["0x20000", "0x22000"], "segments": [{"start": "0x20000", "end": "0x21000", "flags": "r-x"}], "tls": null, "needed": [], "local_scope": [2]}
+  ],
+  "global_scope": [0, 1, 2],
+  "bindings": [
+    {"requester": 0, "symbol": "a", "version": "V1", "provider": 1, "address": "0x10100"}
+  ]
+}
+"#);
+}
+
+#[test]
+fn dot_t () {
+    let (nodes, deps, _, bound) = graph();
+    assert_eq!(dot(&nodes, &deps, &bound), r#"digraph dryad {
+  node [shape=box];
+  n0 [label="", tooltip="/bin/prog @ 0x1000"];
+  n1 [label="liba.so", tooltip="/lib/liba.so @ 0x10000"];
+  n2 [label="libb.so", tooltip="/lib/libb.so @ 0x20000"];
+  n0 -> n1;
+  n0 -> n2 [style=dashed];
+}
+"#);
+}
@@ -123,6 +123,23 @@ impl Debug {
         // this is not documented, but the debugger requires we append, and not cons (contrary to what you would think), since C programmers are all about the speeds - after all, who wants a constant prepend when you can have a linear append?
         LinkMap::append(lm, self.r_map);
     }
+
+    /// Unlinks and frees the entry `add_so` made for `so`, e.g., when a dlopen fails, so the debugger doesn't go looking at memory that's no longer mapped
+    pub unsafe fn remove_so (&mut self, so: &SharedObject) {
+        let mut lm = self.r_map;
+        while !lm.is_null() {
+            if (*lm).l_addr == so.load_bias && (*lm).l_ld == so.dynamic.as_ptr() {
+                let (prev, next) = ((*lm).l_prev, (*lm).l_next);
+                // never the first, "null", entry, so there's always a prev
+                if !prev.is_null() { (*prev).l_next = next; }
+                if !next.is_null() { (*next).l_prev = prev; }
+                drop(CString::from_raw((*lm).l_name as *mut libc::c_char));
+                drop(Box::from_raw(lm));
+                return
+            }
+            lm = (*lm).l_next;
+        }
+    }
 }
 
 unsafe impl Send for Debug {}
@@ -5,6 +5,7 @@
 /// #define ELF_MACHINE_NO_RELA 0
 /// ```
 use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use elf::header::Header;
 use elf::program_header::{self, ProgramHeader};
 use elf::dyn::{self, Dyn};
@@ -13,6 +14,10 @@ use goblin::strtab::Strtab;
 use elf::reloc;
 use elf::gnu_hash::GnuHash;
 use tls;
+use version::{Version, VersionInfo};
+
+/// Undefined section index, i.e., the symbol is a reference, not a definition
+const SHN_UNDEF: u16 = 0;
 
 /// Computes the "load bias", which is normally the base.  However, in older Linux kernel's, 3.13, and whatever runs on travis, I have discovered that the kernel incorrectly maps the vdso with "bad" values.
 ///
@@ -47,6 +52,15 @@ pub unsafe fn compute_load_bias_wrapping(base: usize, phdrs:&[ProgramHeader]) ->
     0
 }
 
+/// A file's identity: its `(st_dev, st_ino)`, and where in it the object starts.  However many names, symlinks or relative paths lead to it,
+/// it's the same object, and like ld.so's `_dl_map_object` we map it only once
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileId {
+    pub dev: u64,
+    pub ino: u64,
+    pub offset: usize,
+}
+
 /// A `SharedObject` is either:
 /// 1. an mmap'd dynamic library which is explicitly loaded by `dryad`
 /// 2. the vdso provided by the kernel
@@ -73,10 +87,23 @@ pub struct SharedObject<'process> {
     pub pltrelocations: &'process[reloc::Rela],
     pub pltgot: *const usize,
     pub gnu_hash: Option<GnuHash<'process>>,
+    /// The biased address of the sysv hash table (`DT_HASH`), if there is one; we only search it when there's no gnu hash table
+    pub hash: Option<usize>,
     pub load_path: Option<String>,
+    /// The file we mapped this from, if it came from one
+    pub file_id: Option<FileId>,
     pub flags: usize,
     pub state_flags: usize,
+    /// Linked with `-z now`, so every PLT slot must be bound before any of its code runs
+    pub bind_now: bool,
+    /// Linked with `-Bsymbolic`, so it searches itself before the global scope
+    pub symbolic: bool,
+    /// The biased `[start, end)` of the `PT_GNU_RELRO` segment, which we make read-only once we're done relocating
+    pub relro: Option<(usize, usize)>,
     pub tls: Option<tls::TlsInfo>,
+    /// How many `thread_local` destructors registered against this object (see `tls::__cxa_thread_atexit_impl`) are yet to run; while there are any, it's pinned
+    pub tls_dtors: AtomicUsize,
+    pub versions: VersionInfo,
     pub link_info: dyn::DynamicInfo,
 }
 
@@ -87,6 +114,297 @@ impl<'process> fmt::Debug for SharedObject<'process> {
     }
 }
 
+/// An object wants eager binding if it has `DF_BIND_NOW` in `DT_FLAGS`, `DF_1_NOW` in `DT_FLAGS_1`, or the (legacy) `DT_BIND_NOW` entry at all
+pub fn is_bind_now (dynamic: &[Dyn], link_info: &dyn::DynamicInfo) -> bool {
+    if link_info.flags as u64 & dyn::DF_BIND_NOW != 0 || link_info.flags_1 as u64 & dyn::DF_1_NOW != 0 {
+        return true
+    }
+    for dyn in dynamic {
+        if dyn.d_tag as u64 == dyn::DT_BIND_NOW {
+            return true
+        }
+    }
+    false
+}
+
+/// Likewise, an object is symbolic if it has `DF_SYMBOLIC` in `DT_FLAGS`, or the (legacy) `DT_SYMBOLIC` entry at all
+pub fn is_symbolic (dynamic: &[Dyn], link_info: &dyn::DynamicInfo) -> bool {
+    if link_info.flags as u64 & dyn::DF_SYMBOLIC != 0 {
+        return true
+    }
+    for dyn in dynamic {
+        if dyn.d_tag as u64 == dyn::DT_SYMBOLIC {
+            return true
+        }
+    }
+    false
+}
+
+/// The biased `[start, end)` a `PT_GNU_RELRO` covers; the start is rounded down to its page, but like ld.so we round the end _down_ too,
+/// since the linker pads relro to a page boundary and a partial trailing page is shared with ordinary writable data
+pub fn relro_range (bias: usize, phdr: &ProgramHeader) -> (usize, usize) {
+    let start = ::utils::page::page_start(phdr.p_vaddr as usize + bias);
+    let end = ::utils::page::page_start(phdr.p_vaddr as usize + phdr.p_memsz as usize + bias);
+    (start, end)
+}
+
+/// The biased `[start, end)` of each `PT_LOAD`; anything the dynamic array points at had better be inside one of these
+struct Segments(Vec<(usize, usize)>);
+
+impl Segments {
+    fn new (load_bias: usize, phdrs: &[ProgramHeader]) -> Segments {
+        let mut ranges = Vec::new();
+        for phdr in phdrs {
+            if phdr.p_type == program_header::PT_LOAD {
+                let start = (phdr.p_vaddr as usize).wrapping_add(load_bias);
+                ranges.push((start, start.wrapping_add(phdr.p_memsz as usize)));
+            }
+        }
+        Segments(ranges)
+    }
+
+    /// Whether all of `[addr, addr + size)` lies within a single segment
+    fn contains (&self, addr: usize, size: usize) -> bool {
+        let end = match addr.checked_add(size) { Some (end) => end, None => return false };
+        self.0.iter().any(|&(start, seg_end)| addr >= start && end <= seg_end)
+    }
+
+    /// The dynamic array must be mapped, and terminated by a `DT_NULL` before the end of its segment, otherwise `dyn::from_raw` walks off into the weeds
+    unsafe fn check_dynamic (&self, name: &str, load_bias: usize, phdr: &ProgramHeader) -> Result<(), String> {
+        let start = (phdr.p_vaddr as usize).wrapping_add(load_bias);
+        if start % ::std::mem::align_of::<Dyn>() != 0 || !self.contains(start, phdr.p_memsz as usize) {
+            return Err (format!("Error: {}: _DYNAMIC array at {:#x} is not within a loaded segment", name, phdr.p_vaddr))
+        }
+        let count = phdr.p_memsz as usize / ::std::mem::size_of::<Dyn>();
+        let dynamic = ::std::slice::from_raw_parts(start as *const Dyn, count);
+        if dynamic.iter().any(|dyn| dyn.d_tag as u64 == dyn::DT_NULL) {
+            Ok (())
+        } else {
+            Err (format!("Error: {}: _DYNAMIC array is not terminated by DT_NULL", name))
+        }
+    }
+
+    fn check_table (&self, name: &str, table: &str, addr: usize, size: usize) -> Result<(), String> {
+        if size == 0 || self.contains(addr, size) {
+            Ok (())
+        } else {
+            Err (format!("Error: {}: {} at {:#x} with size {:#x} is not within a loaded segment", name, table, addr, size))
+        }
+    }
+
+    /// Checks the strtab, symtab, relocation tables, hash tables, PLTGOT and constructor and destructor arrays are mapped and consistent with each other; returns the number of symbols
+    unsafe fn check_tables (&self, name: &str, dynamic: &[Dyn], link_info: &dyn::DynamicInfo, load_bias: usize) -> Result<usize, String> {
+        let strsz = link_info.strsz as usize;
+        if link_info.strtab == 0 || strsz == 0 {
+            return Err (format!("Error: {}: no DT_STRTAB", name))
+        }
+        self.check_table(name, "DT_STRTAB", link_info.strtab, strsz)?;
+        // every string has to end somewhere, so make sure the last one ends before strsz
+        if *((link_info.strtab + strsz - 1) as *const u8) != 0 {
+            return Err (format!("Error: {}: strtab is not NUL terminated", name))
+        }
+
+        if link_info.symtab == 0 {
+            return Err (format!("Error: {}: no DT_SYMTAB", name))
+        }
+        // there's no DT_SYMSZ, so like everyone else we get the symbol count from the hash tables: DT_HASH's nchain is exactly it,
+        // and the last index in DT_GNU_HASH's chains is the last symbol it hashes (the ones before symoffset aren't hashed, but they're still symbols).
+        // Assuming the strtab follows the symtab doesn't work, lld puts .gnu.version, .gnu.version_r and .gnu.hash in between
+        let sysv = match hash_table(dynamic, load_bias) {
+            Some (addr) => Some (self.check_hash(name, addr)?),
+            None => None,
+        };
+        let gnu = match link_info.gnu_hash {
+            Some (addr) => Some (self.check_gnu_hash(name, addr)?),
+            None => None,
+        };
+        let num_syms = match (sysv, gnu) {
+            (Some (nchain), Some (gnu)) if gnu > nchain => return Err (format!("Error: {}: DT_GNU_HASH has {} symbols but DT_HASH only {}", name, gnu, nchain)),
+            (Some (nchain), _) => nchain,
+            (None, Some (gnu)) => gnu,
+            // nothing can be looked up in an object without a hash table, but it can still have relocations against its symbols;
+            // all that's left is the old guess, which holds for binutils at least
+            (None, None) => {
+                if link_info.symtab > link_info.strtab {
+                    return Err (format!("Error: {}: no hash table, and DT_SYMTAB does not precede DT_STRTAB", name))
+                }
+                (link_info.strtab - link_info.symtab) / sym::SIZEOF_SYM
+            }
+        };
+        let symtab_size = num_syms.checked_mul(sym::SIZEOF_SYM).ok_or(format!("Error: {}: too many symbols", name))?;
+        self.check_table(name, "DT_SYMTAB", link_info.symtab, symtab_size)?;
+
+        if link_info.soname >= strsz {
+            return Err (format!("Error: {}: DT_SONAME is past the end of the strtab", name))
+        }
+        for dyn in dynamic {
+            if dyn.d_tag as u64 == dyn::DT_NEEDED && dyn.d_val as usize >= strsz {
+                return Err (format!("Error: {}: DT_NEEDED is past the end of the strtab", name))
+            }
+        }
+
+        #[cfg(target_pointer_width = "32")]
+        self.check_table(name, "DT_REL", link_info.rel, link_info.relsz)?;
+        #[cfg(target_pointer_width = "64")]
+        self.check_table(name, "DT_RELA", link_info.rela, link_info.relasz)?;
+        self.check_table(name, "DT_JMPREL", link_info.jmprel, link_info.pltrelsz)?;
+
+        // the linker writes the link map and the resolver into GOT[1] and GOT[2], so the first three words had better be ours
+        if let Some(pltgot) = link_info.pltgot {
+            self.check_table(name, "DT_PLTGOT", pltgot, 3 * ::std::mem::size_of::<usize>())?;
+        }
+        // and the constructors and destructors are called through whatever these arrays hold
+        self.check_table(name, "DT_INIT_ARRAY", link_info.init_array as usize, link_info.init_arraysz as usize)?;
+        self.check_table(name, "DT_FINI_ARRAY", link_info.fini_array as usize, link_info.fini_arraysz as usize)?;
+        let mut preinit_array = 0;
+        let mut preinit_arraysz = 0;
+        for dyn in dynamic {
+            match dyn.d_tag as u64 {
+                dyn::DT_PREINIT_ARRAY => preinit_array = (dyn.d_val as usize).wrapping_add(load_bias),
+                dyn::DT_PREINIT_ARRAYSZ => preinit_arraysz = dyn.d_val as usize,
+                _ => ()
+            }
+        }
+        self.check_table(name, "DT_PREINIT_ARRAY", preinit_array, preinit_arraysz)?;
+        Ok (num_syms)
+    }
+
+    /// Checks the whole sysv hash table is mapped and that every bucket and chain entry is a symbol; returns nchain, i.e., the number of symbols
+    unsafe fn check_hash (&self, name: &str, addr: usize) -> Result<usize, String> {
+        let word = ::std::mem::size_of::<u32>();
+        self.check_table(name, "DT_HASH", addr, 2 * word)?;
+        let table = addr as *const u32;
+        let nbucket = *table as usize;
+        let nchain = *table.offset(1) as usize;
+        if nbucket == 0 {
+            return Err (format!("Error: {}: malformed DT_HASH header", name))
+        }
+        let size = (2 + nbucket).checked_add(nchain).and_then(|n| n.checked_mul(word)).ok_or(format!("Error: {}: malformed DT_HASH header", name))?;
+        self.check_table(name, "DT_HASH", addr, size)?;
+        let entries = ::std::slice::from_raw_parts(table.offset(2), nbucket + nchain);
+        if entries.iter().any(|&idx| idx as usize >= nchain) {
+            return Err (format!("Error: {}: DT_HASH refers to a symbol past nchain", name))
+        }
+        Ok (nchain)
+    }
+
+    /// Checks the gnu hash table's header, bloom filter and buckets are mapped, and walks every bucket's chain, so the lookups in `find` and `find_versioned`
+    /// never index past the table; returns the number of symbols it implies
+    unsafe fn check_gnu_hash (&self, name: &str, addr: usize) -> Result<usize, String> {
+        let word = ::std::mem::size_of::<u32>();
+        self.check_table(name, "DT_GNU_HASH", addr, 4 * word)?;
+        let table = addr as *const u32;
+        let nbuckets = *table as usize;
+        let symoffset = *table.offset(1) as usize;
+        let bloom_size = *table.offset(2) as usize;
+        if nbuckets == 0 || bloom_size == 0 {
+            return Err (format!("Error: {}: malformed DT_GNU_HASH header", name))
+        }
+        let bloom_bytes = bloom_size.checked_mul(::std::mem::size_of::<usize>()).ok_or(format!("Error: {}: malformed DT_GNU_HASH header", name))?;
+        self.check_table(name, "DT_GNU_HASH bloom filter", addr + 4 * word, bloom_bytes)?;
+        let buckets = addr + 4 * word + bloom_bytes;
+        self.check_table(name, "DT_GNU_HASH buckets", buckets, nbuckets * word)?;
+        let chains = buckets + nbuckets * word;
+        let chain = |i: usize| {
+            let entry = match i.checked_mul(word).and_then(|offset| chains.checked_add(offset)) { Some (entry) => entry, None => return None };
+            if self.contains(entry, word) { Some (*(entry as *const u32)) } else { None }
+        };
+        let len = gnu_hash_len(symoffset, ::std::slice::from_raw_parts(buckets as *const u32, nbuckets), chain)
+            .ok_or(format!("Error: {}: DT_GNU_HASH chain runs past the end of its segment", name))?;
+        // a bucket can start in the middle of the chains we skipped walking, but it'll still stop at the end of the last one, so they'd all better be in the same segment
+        self.check_table(name, "DT_GNU_HASH chains", chains, (len - symoffset) * word)?;
+        Ok (len)
+    }
+}
+
+/// The biased address of the sysv hash table, if there is one
+fn hash_table (dynamic: &[Dyn], load_bias: usize) -> Option<usize> {
+    dynamic.iter().find(|dyn| dyn.d_tag as u64 == dyn::DT_HASH).map(|dyn| (dyn.d_val as usize).wrapping_add(load_bias))
+}
+
+/// The sysv (`DT_HASH`) hash function, from the ELF spec
+fn sysv_hash (name: &str) -> u32 {
+    let mut h = 0u32;
+    for &c in name.as_bytes() {
+        h = (h << 4).wrapping_add(c as u32);
+        let g = h & 0xf000_0000;
+        if g != 0 {
+            h ^= g >> 24;
+        }
+        h &= !g;
+    }
+    h
+}
+
+#[test]
+fn sysv_hash_t () {
+    assert_eq!(sysv_hash(""), 0);
+    assert_eq!(sysv_hash("printf"), 0x077905a6);
+    assert_eq!(sysv_hash("__libc_start_main"), 0x0177ff8e);
+}
+
+/// Whether the gnu hash table's bloom filter says a symbol with gnu hash `hash` might be in it; `false` means it definitely isn't,
+/// which is the answer for most of the objects we search, so it saves walking their chains
+fn bloom_accepts (bloom: &[usize], shift2: u32, hash: u32) -> bool {
+    let bits = ::std::mem::size_of::<usize>() * 8;
+    let word = bloom[(hash as usize / bits) % bloom.len()];
+    let mask = (1 << (hash as usize % bits)) | (1 << (hash.checked_shr(shift2).unwrap_or(0) as usize % bits));
+    word & mask == mask
+}
+
+#[test]
+fn bloom_accepts_t () {
+    let bits = ::std::mem::size_of::<usize>() as u32 * 8;
+    // a two word filter with shift2 = 5, holding just the one hash
+    let hash = 0x1234_5678u32;
+    let mut bloom = [0usize; 2];
+    bloom[((hash / bits) % 2) as usize] = (1 << (hash % bits)) | (1 << ((hash >> 5) % bits));
+    assert!(bloom_accepts(&bloom, 5, hash));
+    assert!(!bloom_accepts(&bloom, 5, hash + 1));
+    // right bits, wrong word
+    assert!(!bloom_accepts(&bloom, 5, hash ^ bits));
+    // and a silly shift2 isn't a panic
+    assert!(!bloom_accepts(&bloom, 40, hash + 1));
+}
+
+/// How many symbols a gnu hash table covers: one past the highest index any of its chains reach, or `symoffset` if they're all empty.
+/// `chain(i)` is the chain entry for symbol `symoffset + i`, or `None` if it isn't mapped, in which case so are we.
+fn gnu_hash_len<F: Fn(usize) -> Option<u32>> (symoffset: usize, buckets: &[u32], chain: F) -> Option<usize> {
+    let mut len = symoffset;
+    for &bucket in buckets {
+        let mut idx = bucket as usize;
+        if idx < symoffset {
+            continue
+        }
+        // chains are laid out one after another in bucket order, so we only need to walk the ones past where we've already been
+        if idx < len {
+            continue
+        }
+        loop {
+            let entry = chain(idx - symoffset)?;
+            if entry & 1 == 1 {
+                break
+            }
+            idx += 1;
+        }
+        len = idx + 1;
+    }
+    Some (len)
+}
+
+#[test]
+fn gnu_hash_len_t () {
+    // symbols 0 and 1 unhashed, bucket 0 empty, bucket 1 is symbols 2 and 3, bucket 2 is symbol 4
+    let chains = [0x10, 0x21, 0x31];
+    let chain = |i: usize| chains.get(i).cloned();
+    assert_eq!(gnu_hash_len(2, &[0, 2, 4], &chain), Some (5));
+    assert_eq!(gnu_hash_len(2, &[0, 0, 0], &chain), Some (2));
+    // the last chain never ends
+    let chains = [0x10, 0x21, 0x30];
+    let chain = |i: usize| chains.get(i).cloned();
+    assert_eq!(gnu_hash_len(2, &[0, 2, 4], &chain), None);
+}
+
 macro_rules! gnu_hash {
     ($link_info:ident, $symtab:ident) => {
         if let Some(addr) = $link_info.gnu_hash {
@@ -101,16 +419,102 @@ impl<'process> SharedObject<'process> {
         &self.strtab[self.link_info.soname]
     }
 
+    /// The path we loaded this object from, or its soname if it wasn't loaded from disk (e.g., the vdso)
+    pub fn path (&self) -> &str {
+        match self.load_path {
+            Some (ref path) => path,
+            None => self.name(),
+        }
+    }
+
+    /// The symbol version (if any) the undefined symbol at `sym_idx` in this object requires of its provider
+    pub fn requested_version (&self, sym_idx: usize) -> Option<Version> {
+        unsafe { self.versions.requested(&self.strtab, sym_idx) }
+    }
+
     /// Assumes the object referenced by the ptr has already been mmap'd or loaded into memory some way
-    pub unsafe fn from_raw (ptr: usize) -> SharedObject<'process> {
+    pub unsafe fn from_raw (ptr: usize) -> Result<SharedObject<'process>, String> {
         let header = &*(ptr as *const Header);
         let phdrs = ProgramHeader::from_raw_parts((header.e_phoff as usize + ptr) as *const ProgramHeader, header.e_phnum as usize);
         let load_bias = compute_load_bias_wrapping(ptr, &phdrs);
-        let dynamic = dyn::from_phdrs(load_bias, phdrs).unwrap();
-        let link_info = dyn::DynamicInfo::new(&dynamic, load_bias);
-        let num_syms = (link_info.strtab - link_info.symtab) / sym::SIZEOF_SYM;
+        let so = SharedObject::from_mapped("<vdso>", load_bias, phdrs)?;
+        Ok (SharedObject {
+            load_bias: ptr,
+            // TODO: should probably check for tls, even tho this currently only used for linux gate
+            .. so
+        })
+    }
+
+    pub fn from_executable (name: &'static str, phdr_addr: usize, phnum: usize, lachesis: &mut tls::Lachesis) -> Result<SharedObject<'process>, String> {
+        unsafe {
+            let addr = phdr_addr as *const ProgramHeader;
+            let phdrs = ProgramHeader::from_raw_parts(addr, phnum);
+
+            let mut load_bias = 0usize;
+            let mut tls_phdr = None;
+            let mut relro_phdr = None;
+            for phdr in phdrs {
+                match phdr.p_type {
+                    program_header::PT_PHDR => {
+                        load_bias = phdr_addr - phdr.p_vaddr as usize;
+                    },
+                    program_header::PT_TLS => {
+                        tls_phdr = Some(phdr);
+                    },
+                    program_header::PT_GNU_RELRO => {
+                        relro_phdr = Some(phdr);
+                    },
+                    _ => ()
+                }
+            }
+
+            let so = SharedObject::from_mapped(name, load_bias, phdrs)?;
+            if so.pltgot.is_null() {
+                return Err (format!("Error: executable {} has no pltgot, aborting", name))
+            }
+
+            let tls = if let Some(phdr) = tls_phdr {
+                Some(lachesis.push_module(name, load_bias, phdr))
+            } else { None };
+
+            // PT_PHDR needn't come first, so we have to wait for the bias
+            let relro = relro_phdr.map(|phdr| relro_range(load_bias, phdr));
+
+            Ok (SharedObject {
+                load_path: Some (name.to_string()), // TODO: make absolute?,
+                relro: relro,
+                tls: tls,
+                .. so
+            })
+        }
+    }
+
+    /// The checked construction path every `SharedObject` goes through: given an image whose `PT_LOAD` segments are already mapped at `load_bias`,
+    /// finds the dynamic array and builds our slices from it, but only after making sure every table it points at (and every string index into the strtab)
+    /// lies within the mapped segments.  A corrupt or malicious object gets an `Err`, never a wild read.
+    ///
+    /// The result has no load path, tls or relro; callers fill in whatever they know about with struct update syntax.
+    pub unsafe fn from_mapped (name: &str, load_bias: usize, phdrs: &'process [ProgramHeader]) -> Result<SharedObject<'process>, String> {
+        let segments = Segments::new(load_bias, phdrs);
+
+        let dynamic_phdr = match phdrs.iter().find(|phdr| phdr.p_type == program_header::PT_DYNAMIC) {
+            Some (phdr) => phdr,
+            None => return Err (format!("Error: {} has no _DYNAMIC array", name)),
+        };
+        segments.check_dynamic(name, load_bias, dynamic_phdr)?;
+
+        let dynamic = dyn::from_raw(load_bias, dynamic_phdr.p_vaddr as usize);
+        let link_info = dyn::DynamicInfo::new(dynamic, load_bias);
+        let num_syms = segments.check_tables(name, dynamic, &link_info, load_bias)?;
+
         let symtab = sym::from_raw(link_info.symtab as *const sym::Sym, num_syms);
         let strtab = Strtab::from_raw(link_info.strtab as *const u8, link_info.strsz as usize, 0x0);
+        for symbol in symtab {
+            if symbol.st_name as usize >= link_info.strsz as usize {
+                return Err (format!("Error: {}: symbol name index {:#x} is past the end of the strtab", name, symbol.st_name))
+            }
+        }
+
         let libs = dyn::get_needed(dynamic, &strtab, link_info.needed_count);
         #[cfg(target_pointer_width = "32")]
         let relocations = reloc::from_raw_rel(link_info.rel as *const reloc::Rel, link_info.relsz);
@@ -120,9 +524,20 @@ impl<'process> SharedObject<'process> {
         let relocations = reloc::from_raw_rela(link_info.rela as *const reloc::Rela, link_info.relasz);
         #[cfg(target_pointer_width = "64")]
         let pltrelocations = reloc::from_raw_rela(link_info.jmprel as *const reloc::Rela, link_info.pltrelsz);
+        for reloc in relocations.iter().chain(pltrelocations.iter()) {
+            if reloc::r_sym(reloc.r_info) as usize >= num_syms {
+                return Err (format!("Error: {}: relocation refers to symbol {} but there are only {}", name, reloc::r_sym(reloc.r_info), num_syms))
+            }
+        }
+
+        let versions = VersionInfo::new(dynamic, load_bias);
+        versions.check(num_syms, link_info.strsz as usize, |addr, size| segments.contains(addr, size))
+            .map_err(|e| format!("Error: {}: {}", name, e))?;
+
+        // musl doesn't have a PLTGOT, for example
         let pltgot = if let Some(addr) = link_info.pltgot { addr } else { 0 };
-        SharedObject {
-            load_bias: ptr,
+        Ok (SharedObject {
+            load_bias: load_bias,
             map_begin: 0,
             map_end: 0,
             libs: libs,
@@ -134,84 +549,40 @@ impl<'process> SharedObject<'process> {
             pltrelocations: pltrelocations,
             pltgot: pltgot as *const usize,
             gnu_hash: gnu_hash!(link_info, symtab),
+            hash: hash_table(dynamic, load_bias),
             load_path: None,
+            file_id: None,
             flags: link_info.flags as usize,
             state_flags: link_info.flags_1 as usize,
-            tls: None, // TODO: should probably check for tls, even tho this currently only used for linux gate
+            bind_now: is_bind_now(dynamic, &link_info),
+            symbolic: is_symbolic(dynamic, &link_info),
+            relro: None,
+            tls: None,
+            tls_dtors: AtomicUsize::new(0),
+            versions: versions,
             link_info: link_info,
-        }
-
+        })
     }
 
-    pub fn from_executable (name: &'static str, phdr_addr: usize, phnum: usize, lachesis: &mut tls::Lachesis) -> Result<SharedObject<'process>, String> {
-        unsafe {
-            let addr = phdr_addr as *const ProgramHeader;
-            let phdrs = ProgramHeader::from_raw_parts(addr, phnum);
-
-            let mut load_bias = 0usize;
-            let mut dynamic_vaddr = None;
-            let mut tls_phdr = None;
-            for phdr in phdrs {
-                match phdr.p_type {
-                    program_header::PT_PHDR => {
-                        load_bias = phdr_addr - phdr.p_vaddr as usize;
-                    },
-                    program_header::PT_DYNAMIC => {
-                        dynamic_vaddr = Some(phdr.p_vaddr as usize);
-                    },
-                    program_header::PT_TLS => {
-                        tls_phdr = Some(phdr);
-                    },
-                    _ => ()
-                }
-            }
+    /// Makes the `PT_GNU_RELRO` region read-only; call only once every relocation targeting it has been written
+    pub fn protect_relro (&self) -> bool {
+        match self.relro {
+            Some ((start, end)) if start < end => ::utils::mmap::protect(start, end, ::utils::mmap::PROT_READ),
+            _ => true
+        }
+    }
 
-            let tls = if let Some(phdr) = tls_phdr {
-                Some(lachesis.push_module(name, load_bias, phdr))
-            } else { None };
+    /// Whether `addr` is in one of our `PT_LOAD` segments
+    pub fn contains (&self, addr: usize) -> bool {
+        self.phdrs.iter().any(|phdr| {
+            let start = (phdr.p_vaddr as usize).wrapping_add(self.load_bias);
+            phdr.p_type == program_header::PT_LOAD && addr >= start && addr - start < phdr.p_memsz as usize
+        })
+    }
 
-            if let Some(vaddr) = dynamic_vaddr {
-                let dynamic = dyn::from_raw(load_bias, vaddr);
-                let link_info = dyn::DynamicInfo::new(dynamic, load_bias);
-                // TODO: swap out the link_info syment with compile time constant SIZEOF_SYM?
-                let num_syms = (link_info.strtab - link_info.symtab) / link_info.syment; // this _CAN'T_ generally be valid; but rdr has been doing it and scans every linux shared object binary without issue... so it must be right!
-                let symtab = sym::from_raw(link_info.symtab as *const sym::Sym, num_syms);
-                let strtab = Strtab::from_raw(link_info.strtab as *const u8, link_info.strsz, 0x0);
-                let libs = dyn::get_needed(dynamic, &strtab, link_info.needed_count);
-                #[cfg(target_pointer_width = "32")]
-                let relocations = reloc::from_raw_rel(link_info.rel as *const reloc::Rel, link_info.relsz);
-                #[cfg(target_pointer_width = "32")]
-                let pltrelocations = reloc::from_raw_rel(link_info.jmprel as *const reloc::Rel, link_info.pltrelsz);
-                #[cfg(target_pointer_width = "64")]
-                let relocations = reloc::from_raw_rela(link_info.rela as *const reloc::Rela, link_info.relasz);
-                #[cfg(target_pointer_width = "64")]
-                let pltrelocations = reloc::from_raw_rela(link_info.jmprel as *const reloc::Rela, link_info.pltrelsz);
-                // TODO: fail with Err, not panic
-                let pltgot = link_info.pltgot.expect("Error executable has no pltgot, aborting") as *const usize;
-                Ok (SharedObject {
-                    load_bias: load_bias,
-                    map_begin: 0,
-                    map_end: 0,
-                    libs: libs,
-                    phdrs: phdrs,
-                    dynamic: dynamic,
-                    symtab: symtab,
-                    strtab: strtab,
-                    relocations: relocations,
-                    pltrelocations: pltrelocations,
-                    pltgot: pltgot,
-                    gnu_hash: gnu_hash!(link_info, symtab),
-                    load_path: Some (name.to_string()), // TODO: make absolute?,
-                    flags: link_info.flags as usize,
-                    state_flags: link_info.flags_1 as usize,
-                    tls: tls,
-                    link_info: link_info,
-                })
-
-            } else {
-                Err (format!("Error: executable {} has no _DYNAMIC array", name))
-            }
-        }
+    /// Whether some thread still has a `thread_local` destructor to run in here, in which case we mustn't unmap it
+    pub fn pinned (&self) -> bool {
+        self.tls_dtors.load(Ordering::Acquire) != 0
     }
 
     /// This is used by dryad's runtime symbol resolution
@@ -219,8 +590,82 @@ impl<'process> SharedObject<'process> {
 //        println!("<{}.find> finding symbol: {}", self.name, symbol);
         match self.gnu_hash {
             Some (ref gnu_hash) => gnu_hash.find(name, hash, &self.strtab),
-            None => None
+            // no gnu hash table, so look in the sysv one, if there is one
+            None => self.find_versioned(name, hash, None)
+        }
+    }
+
+    /// Like `find`, but only returns a definition (never a reference or a local) which satisfies `version`.
+    /// We have to walk the hash chain ourselves, since the same name can appear several times with different versions (e.g., `memcpy@GLIBC_2.2.5` and `memcpy@@GLIBC_2.14`),
+    /// and `GnuHash::find` stops at the first.
+    /// `hash` is the name's gnu hash; objects with only a sysv hash table (e.g., linked with `--hash-style=sysv`) get searched with the sysv one instead.
+    pub fn find_versioned (&self, name: &str, hash: u32, version: Option<&Version>) -> Option<&sym::Sym> {
+        match (self.link_info.gnu_hash, self.hash) {
+            (Some (table), _) => unsafe { self.find_gnu(table as *const u32, name, hash, version) },
+            (None, Some (table)) => unsafe { self.find_sysv(table as *const u32, name, version) },
+            (None, None) => None,
+        }
+    }
+
+    /// Whether the symbol at `idx` is a definition of `name` which satisfies `version`
+    fn defines (&self, idx: usize, name: &str, version: Option<&Version>) -> bool {
+        let symbol = &self.symtab[idx];
+        symbol.st_shndx != SHN_UNDEF
+            && sym::st_bind(symbol.st_info) != sym::STB_LOCAL
+            && &self.strtab[symbol.st_name as usize] == name
+            && self.versions.matches(&self.strtab, idx, version)
+    }
+
+    /// `find_versioned` in the gnu hash table at `table`; `check_gnu_hash` made sure none of this reads past it
+    unsafe fn find_gnu (&self, table: *const u32, name: &str, hash: u32, version: Option<&Version>) -> Option<&sym::Sym> {
+        let nbuckets = *table;
+        let symoffset = *table.offset(1) as usize;
+        let bloom_size = *table.offset(2) as usize;
+        let shift2 = *table.offset(3);
+        let bloom = ::std::slice::from_raw_parts(table.offset(4) as *const usize, bloom_size);
+        let buckets = table.offset(4 + (bloom_size * (::std::mem::size_of::<usize>() / 4)) as isize);
+        let chains = buckets.offset(nbuckets as isize);
+        if nbuckets == 0 || bloom_size == 0 || !bloom_accepts(bloom, shift2, hash) {
+            return None
+        }
+        let mut idx = *buckets.offset((hash % nbuckets) as isize) as usize;
+        if idx < symoffset {
+            return None
+        }
+        loop {
+            let chain_hash = *chains.offset((idx - symoffset) as isize);
+            if (chain_hash | 1) == (hash | 1) && self.defines(idx, name, version) {
+                return Some (&self.symtab[idx])
+            }
+            if chain_hash & 1 == 1 {
+                break
+            }
+            idx += 1;
+        }
+        None
+    }
+
+    /// `find_versioned` in the sysv hash table at `table`; `check_hash` made sure every bucket and chain entry is a symbol, but not that the chains end,
+    /// so we give up after nchain steps
+    unsafe fn find_sysv (&self, table: *const u32, name: &str, version: Option<&Version>) -> Option<&sym::Sym> {
+        let nbucket = *table;
+        let nchain = *table.offset(1) as usize;
+        let buckets = table.offset(2);
+        let chains = buckets.offset(nbucket as isize);
+        if nbucket == 0 {
+            return None
+        }
+        let mut idx = *buckets.offset((sysv_hash(name) % nbucket) as isize) as usize;
+        let mut steps = 0;
+        // chains end at STN_UNDEF, i.e., 0
+        while idx != 0 && steps < nchain {
+            if self.defines(idx, name, version) {
+                return Some (&self.symtab[idx])
+            }
+            idx = *chains.offset(idx as isize) as usize;
+            steps += 1;
         }
+        None
     }
 
 }
@@ -1,6 +1,7 @@
 use auxv;
 use utils::*;
 
+use std::ptr;
 use std::slice;
 
 #[derive(Debug)]
@@ -29,6 +30,33 @@ impl<'b> KernelBlock<'b> {
         None
     }
 
+    /// Overwrites the auxv entry of type `t` in place, returning whether there was one
+    pub unsafe fn setauxval(&self, t:usize, val: usize) -> bool {
+        let mut ptr = self.auxv as *mut auxv::Auxv;
+        while (*ptr).a_type != auxv::AT_NULL {
+            if (*ptr).a_type == t {
+                (*ptr).a_val = val;
+                return true
+            }
+            ptr = ptr.offset(1);
+        }
+        false
+    }
+
+    /// Drops the first `count` arguments as if they'd never been passed, by decrementing argc and moving everything after them (the rest of argv, envp and the auxv) down the stack in place;
+    /// the strings themselves stay where they are.  This is for when we're run as `dryad.so.1 ./program args...`, so the program finds its argc, argv, envp and auxv right where crt1 expects them.
+    /// `args` is the pointer this block was made from, and this block (and anything borrowed from it) is stale afterwards; make a new one.
+    pub unsafe fn skip_args(&self, args: *mut usize, count: usize) {
+        let mut auxc = 0;
+        while (*self.auxv.offset(auxc)).a_type != auxv::AT_NULL {
+            auxc += 1;
+        }
+        // argv and its null, envp and its null (envc counts it already), and the auxv with its AT_NULL
+        let words = self.argc as usize + 1 + self.envc as usize + (auxc as usize + 1) * 2;
+        *args = self.argc as usize - count;
+        ptr::copy(args.offset(1 + count as isize), args.offset(1), words - count);
+    }
+
     pub fn getenv<'a>(&self, name:&'static str) -> Option<&'a str> {
         for i in 0..self.envc - 1 {
             let evar = str_at(self.env[i as usize], 0);
@@ -24,15 +24,26 @@ pub use goblin::elf32 as elf;
 mod auxv;
 mod kernel_block;
 #[macro_use] mod utils;
-mod image;
+mod debug;
+pub mod image;
 
-mod loader;
+mod lock;
+pub mod loader;
 mod tls;
+mod version;
 mod relocation;
+mod scope;
+mod dump;
+mod trace;
+pub mod stats;
 pub mod runtime;
 pub mod linker;
 pub mod gdb;
+pub mod dlfcn;
 
+use std::mem;
+use elf::header::Header;
+use elf::program_header::{self, ProgramHeader};
 use kernel_block::KernelBlock;
 use linker::Linker;
 use utils::*;
@@ -48,46 +59,60 @@ extern {
     fn _start();
 }
 
+/// Our base (the address of our elf header) when the kernel ran us as the program, and so gave us no `AT_BASE`:
+/// `AT_PHDR` is where our program headers are, and `PT_PHDR` says where they are relative to the base.
+/// We haven't relocated ourselves yet, so nothing fancy in here.
+unsafe fn base_from_phdrs (block: &KernelBlock) -> usize {
+    let addr = block.getauxval(auxv::AT_PHDR).unwrap();
+    let phnum = block.getauxval(auxv::AT_PHNUM).unwrap();
+    let phdrs = ProgramHeader::from_raw_parts(addr as *const ProgramHeader, phnum);
+    for phdr in phdrs {
+        if phdr.p_type == program_header::PT_PHDR {
+            return addr - phdr.p_vaddr as usize
+        }
+    }
+    // no PT_PHDR, so trust they come straight after the elf header, which is where ld puts them anyway
+    addr - mem::size_of::<Header>()
+}
+
 #[no_mangle]
 pub extern fn dryad_init (raw_args: *const usize) -> usize {
 
     // the linker is currently tied to the lifetime of the kernel block... but really it's static
     let block = KernelBlock::new(raw_args);
-    let linker_base = block.getauxval(auxv::AT_BASE).unwrap();
     let entry  = block.getauxval(auxv::AT_ENTRY).unwrap();
 
+    // because it's _tradition_
+    // (https://fossies.org/dox/glibc-2.22/rtld_8c_source.html)
+    // line 786:
+    // > Ho ho.  We are not the program interpreter!  We are the program itself!
+    // i.e., someone ran `./dryad.so.1 ./program args...`, so once we've relocated ourselves we load the program just like the kernel would have, and carry on as its interpreter
     let start_addr = _start as *const usize as usize;
-    if start_addr == entry {
-        utils::set_panic();
-        // because it's _tradition_
-        // (https://fossies.org/dox/glibc-2.22/rtld_8c_source.html)
-        // line 786:
-        // > Ho ho.  We are not the program interpreter!  We are the program itself!
-        println!("-=|dryad====-");
-        println!("Ho ho.  We are not the program interpreter!  We are the program itself!");
-        let ret = {
-            if block.argc >= 2 {
-                let binary = str_at(block.argv[1], 0);
-                println!("binary: {:?}", binary);
-                // this is not accessible to use right now because we don't use endian_fd;
-                // this is fine since we don't want to parse the binary anyway, we want to load it;
-                // but this functionality isn't quite supported yet, need to add better loader api to allow this,
-                // so for now, we will comment this out
-                // let elf = goblin::elf::Elf::from(Path::new(binary)).expect(&format!("Cannot load binary {}", binary));
-                0
-            } else {
-                println!("usage: dryad <path/to/bin>");
-                1
-            }
-        };
-        _exit(ret);
-        return 0xd47ad // to make compiler happy
-    }
+    let direct = start_addr == entry;
+    let linker_base = if direct {
+        unsafe { base_from_phdrs(&block) }
+    } else {
+        block.getauxval(auxv::AT_BASE).unwrap()
+    };
 
     match Linker::new(linker_base, &block) {
-        Ok (dryad) => {
+        Ok (mut dryad) => {
+            let entry = if direct {
+                match unsafe { dryad.exec(raw_args as *mut usize, &block) } {
+                    Ok (entry) => entry,
+                    Err (msg) => {
+                        write_err(&format!("{}\n", msg));
+                        _exit(1);
+                        return 0xd47ad
+                    }
+                }
+            } else {
+                entry
+            };
+            // when run directly, `exec` moved everything in the block down the stack
+            let block = KernelBlock::new(raw_args);
             if let Err(msg) = dryad.link(&block) {
-                println!("{}", msg);
+                write_err(&format!("{}\n", msg));
                 _exit(1);
                 0xd47ad
             } else {
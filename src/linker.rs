@@ -5,12 +5,17 @@
 // TODO:
 // 1. fix TLS
 // 2. determine reason for libc crashes again :/
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::boxed::Box;
 use std::fmt;
 use std::mem;
+use std::ptr;
 use std::fs::File;
-use std::path::Path;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::os::unix::io::RawFd;
+use std::sync::atomic::{AtomicBool, Ordering, ATOMIC_BOOL_INIT};
+use libc;
 
 extern crate crossbeam;
 
@@ -21,6 +26,12 @@ use elf::reloc;
 use elf::sym;
 use loader;
 use image::{self, SharedObject};
+use scope::{self, Scopes};
+use dlfcn;
+use debug;
+use stats;
+use dump;
+use trace;
 use elf::gnu_hash;
 
 use gdb;
@@ -30,21 +41,69 @@ use auxv;
 use runtime;
 use tls;
 use relocation;
+use lock;
+use version::Version;
 
 //thread_local!(static FOO: u32 = 0xdeadbeef);
 
-/// The internal config the dynamic linker generates from the environment variables it receives.
+/// The internal config the dynamic linker generates from the environment variables it receives, and when run directly, its command line options.
+#[derive(Default)]
 struct Config<'a> {
     show_auxv: bool,
     bind_now: bool,
-    debug: bool,
+    bind_not: bool,
+    /// `LD_DEBUG`: the categories to print, see `debug`
+    debug: &'a str,
+    /// `LD_DEBUG_OUTPUT`: write the debug output to `<this>.<pid>` instead of stderr
+    debug_output: Option<&'a str>,
     secure: bool,
     verbose: bool,
     trace_loaded_objects: bool,
+    /// `LD_TRACE_PRELINKING`: trace in prelink's format instead of ldd's
+    trace_prelinking: bool,
+    /// `LD_WARN`: when tracing, relocate too, and report undefined symbols, i.e., `ldd -d`, or with `LD_BIND_NOW`, `ldd -r`
+    warn: bool,
     library_path: Vec<&'a str>,
-    preload: &'a[&'a str]
+    preload: Vec<&'a str>,
+    /// `--verify`: only check we could run the program, and exit
+    verify: bool,
+    /// `--inhibit-cache`; we never read /etc/ld.so.cache anyway
+    inhibit_cache: bool,
+    /// `--inhibit-rpath`: objects whose `DT_RPATH`/`DT_RUNPATH` to ignore; we don't do either yet, so this is just for compatibility
+    inhibit_rpath: Vec<&'a str>,
+    /// `--argv0`: what the program gets as `argv[0]` instead of its path
+    argv0: Option<&'a str>,
+    /// `--audit`: rtld-audit modules, which we don't support, so we say so and ignore them
+    audit: Vec<&'a str>,
+    /// `LD_DRYAD_DUMP` or `--dump`: where to write the link state once we're done, see `dump`
+    dump: Option<&'a str>,
+    /// `LD_DRYAD_TRACE`: where to write a timeline of everything we did at exit, see `trace`
+    trace: Option<&'a str>,
 }
 
+/// What the command line asked for when we're run directly; see `Config::parse_args`
+#[derive(Debug, PartialEq)]
+enum Invocation {
+    /// Run the program at this index in the arguments
+    Run (usize),
+    Help,
+}
+
+const USAGE: &'static str = "usage: dryad.so.1 [OPTION]... PROGRAM [ARGUMENT]...
+Loads and runs PROGRAM with dryad as its dynamic linker, whatever its PT_INTERP is.
+
+  --list                 list the objects PROGRAM needs and where they were found, like ldd, instead of running it
+  --verify               exit 0 if PROGRAM is a dynamic program dryad can run, 1 if it's static, 2 if it's anything else
+  --library-path PATH    search the colon separated PATH instead of LD_LIBRARY_PATH
+  --preload LIST         also preload the objects in the colon or space separated LIST, after any in LD_PRELOAD
+  --inhibit-cache        don't use /etc/ld.so.cache (dryad never does)
+  --inhibit-rpath LIST   ignore the RPATH and RUNPATH of the objects in the colon separated LIST
+  --argv0 NAME           give PROGRAM NAME as argv[0] instead of its path
+  --audit LIST           use the rtld-audit modules in the colon separated LIST (unsupported; ignored)
+  --dump FILE            write the objects, scopes and symbol bindings made before PROGRAM runs to FILE as JSON, or the dependency graph as DOT if FILE ends in .dot
+  --help                 show this and exit
+";
+
 impl<'a> Config<'a> {
     pub fn new<'b> (block: &'b kernel_block::KernelBlock) -> Config<'b> {
         // Must be non-null or not in environment to be "false".  See ELF spec, page 42:
@@ -53,18 +112,29 @@ impl<'a> Config<'a> {
             var != "" } else { false };
         let bind_now = if let Some (var) = block.getenv("LD_BIND_NOW") {
             var != "" } else { false };
-        let debug = if let Some (var) = block.getenv("LD_DEBUG") {
+        let bind_not = if let Some (var) = block.getenv("LD_BIND_NOT") {
             var != "" } else { false };
-        // because travis is a PoS
-        let debug = if let Some (var) = block.getenv("LD_DRYAD_DEBUG") {
-            var == "1" } else { true };
          // TODO: FIX THIS IS NOT VALID and massively unsafe
         let secure = block.getauxval(auxv::AT_SECURE).unwrap() != 0;
+        // LD_DRYAD_DEBUG=1 is the old way of saying "everything"
+        let debug = match (block.getenv("LD_DEBUG"), block.getenv("LD_DRYAD_DEBUG")) {
+            (Some (var), _) => var,
+            (None, Some ("1")) => "all",
+            _ => "",
+        };
+        // like glibc, a setuid program doesn't get to write files wherever the environment says
+        let debug_output = if secure { None } else { block.getenv("LD_DEBUG_OUTPUT").and_then(|var| if var != "" { Some (var) } else { None }) };
         // TODO: add different levels of verbosity
         let verbose = if let Some (var) = block.getenv("LD_VERBOSE") {
             var != "" } else { false };
         let trace_loaded_objects = if let Some (var) = block.getenv("LD_TRACE_LOADED_OBJECTS") {
             var != "" } else { false };
+        let trace_prelinking = if let Some (var) = block.getenv("LD_TRACE_PRELINKING") {
+            var != "" } else { false };
+        let warn = if let Some (var) = block.getenv("LD_WARN") {
+            var != "" } else { false };
+        let dump = if secure { None } else { block.getenv("LD_DRYAD_DUMP").and_then(|var| if var != "" { Some (var) } else { None }) };
+        let trace = if secure { None } else { block.getenv("LD_DRYAD_TRACE").and_then(|var| if var != "" { Some (var) } else { None }) };
         let library_path =
             if let Some (paths) = block.getenv("LD_LIBRARY_PATH") {
                 // we don't need to allocate since technically the strings are preallocated in the environment variable, but being lazy for now
@@ -77,40 +147,203 @@ impl<'a> Config<'a> {
             } else { 
                 vec!["/usr/lib"]
             };
+        // like ld.so, separated by spaces or colons; in secure mode only bare names, which we then only search for in the trusted directories
+        let preload =
+            if let Some (libs) = block.getenv("LD_PRELOAD") {
+                libs.split(|c| c == ':' || c == ' ').filter(|lib| *lib != "" && !(secure && lib.contains('/'))).collect()
+            } else {
+                vec![]
+            };
         Config {
             show_auxv: show_auxv,
             bind_now: bind_now,
+            bind_not: bind_not,
             debug: debug,
+            debug_output: debug_output,
             secure: secure,
             verbose: verbose,
-            trace_loaded_objects: trace_loaded_objects,
+            trace_loaded_objects: trace_loaded_objects || trace_prelinking,
+            trace_prelinking: trace_prelinking,
+            warn: warn,
             //TODO: finish path logics
             library_path: library_path,
-            preload: &[],
+            preload: preload,
+            dump: dump,
+            trace: trace,
+            .. Config::default()
+        }
+    }
+
+    /// The argument to the option at `args[*i]`, moving `i` onto it
+    fn value (args: &[&'a str], i: &mut usize) -> Result<&'a str, String> {
+        *i += 1;
+        args.get(*i).cloned().ok_or(format!("Error: option {} requires an argument", args[*i - 1]))
+    }
+
+    /// Parses ld.so's options from `args` (our arguments, i.e., without our own name) up to the program's path, overriding whatever the environment said,
+    /// so a program can be run without anything leaking in from (or having to be put into) the environment.
+    pub fn parse_args (&mut self, args: &[&'a str]) -> Result<Invocation, String> {
+        let mut i = 0;
+        while i < args.len() {
+            let option = args[i];
+            if !option.starts_with("--") {
+                return Ok (Invocation::Run (i))
+            }
+            match option {
+                "--list" => self.trace_loaded_objects = true,
+                "--verify" => self.verify = true,
+                "--library-path" => {
+                    self.library_path = Config::value(args, &mut i)?.split(':').filter(|dir| *dir != "").collect();
+                    self.library_path.push("/usr/lib");
+                },
+                "--preload" => {
+                    let libs = Config::value(args, &mut i)?;
+                    self.preload.extend(libs.split(|c| c == ':' || c == ' ').filter(|lib| *lib != ""));
+                },
+                "--inhibit-cache" => self.inhibit_cache = true,
+                "--inhibit-rpath" => self.inhibit_rpath = Config::value(args, &mut i)?.split(':').filter(|lib| *lib != "").collect(),
+                "--argv0" => self.argv0 = Some (Config::value(args, &mut i)?),
+                "--audit" => self.audit.extend(Config::value(args, &mut i)?.split(':').filter(|lib| *lib != "")),
+                "--dump" => self.dump = Some (Config::value(args, &mut i)?),
+                "--help" => return Ok (Invocation::Help),
+                _ => return Err (format!("Error: unrecognized option {}\n{}", option, USAGE)),
+            }
+            i += 1;
         }
+        Err (format!("Error: no program to run\n{}", USAGE))
     }
 }
 
+#[test]
+fn parse_args_t () {
+    let mut config = Config { library_path: vec!["/from/env", "/usr/lib"], preload: vec!["libenv.so"], .. Config::default() };
+    let args = ["--library-path", "/a:/b", "--preload", "liba.so:libb.so", "--argv0", "foo", "--list", "--dump", "/tmp/prog.json", "./prog", "--verify", "arg"];
+    assert_eq!(config.parse_args(&args), Ok (Invocation::Run (9)));
+    assert_eq!(config.library_path, vec!["/a", "/b", "/usr/lib"]);
+    assert_eq!(config.preload, vec!["libenv.so", "liba.so", "libb.so"]);
+    assert_eq!(config.argv0, Some ("foo"));
+    assert_eq!(config.dump, Some ("/tmp/prog.json"));
+    assert!(config.trace_loaded_objects);
+    // anything after the program is the program's
+    assert!(!config.verify);
+
+    let mut config = Config::default();
+    assert_eq!(config.parse_args(&["--inhibit-cache", "--help", "./prog"]), Ok (Invocation::Help));
+    assert!(config.inhibit_cache);
+    assert!(config.parse_args(&["--argv0"]).is_err());
+    assert!(config.parse_args(&["--bogus", "./prog"]).is_err());
+    assert!(config.parse_args(&["--list"]).is_err());
+}
+
 impl<'a> fmt::Debug for Config<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "bind_now: {} debug: {} secure: {} verbose: {} trace_loaded_objects: {} library_path: {:#?} preload: {:#?}",
+        write!(f, "bind_now: {} bind_not: {} debug: {:?} debug_output: {:?} secure: {} verbose: {} trace_loaded_objects: {} trace_prelinking: {} warn: {} library_path: {:#?} preload: {:#?} verify: {} inhibit_cache: {} inhibit_rpath: {:?} argv0: {:?} audit: {:?} dump: {:?} trace: {:?}",
                self.bind_now,
+               self.bind_not,
                self.debug,
+               self.debug_output,
                self.secure,
                self.verbose,
                self.trace_loaded_objects,
+               self.trace_prelinking,
+               self.warn,
                self.library_path,
-               self.preload
+               self.preload,
+               self.verify,
+               self.inhibit_cache,
+               self.inhibit_rpath,
+               self.argv0,
+               self.audit,
+               self.dump,
+               self.trace
                )
     }
 }
 
-/*
+/// Runs the destructors of every object we constructed, `DT_FINI_ARRAY` in reverse and then `DT_FINI`, in the reverse of the order their constructors ran.
+///
+/// Our `_start` passes this to the program's in `rdx`, and (glibc's) crt1 hands it to `__libc_start_main`, which `atexit`s it before the executable's constructors and `main` run,
+/// so it runs after everything they `atexit`, i.e., after the program's `atexit` handlers and its C++ static destructors.
+/// It is _not_ the first thing registered though: the libraries' constructors ran before `_start`, so whatever they `atexit` comes earlier, and `exit` gets to it after us.
+/// With glibc that's mostly moot, since a library's `atexit` and `__cxa_atexit` calls carry its `__dso_handle`, and the `__cxa_finalize` its crtstuff calls from `DT_FINI_ARRAY`
+/// runs them here, as part of its destructors, in the reverse of the order the libraries were constructed; that's how ld.so gets away without knowing about any of it either.
+/// What's left over (say, a library without crtstuff) runs after every library's destructors, so all we guarantee is: the program's handlers, then ours, in reverse init order, and then the rest.
+/// musl's crt1 ignores `rdx` altogether, so there this only runs if something else calls it.
+///
+/// The executable's own destructors are crt1's job, like its constructors.
 #[no_mangle]
 pub extern fn _dryad_fini() {
-    return
+    static FINALIZED: AtomicBool = ATOMIC_BOOL_INIT;
+    if FINALIZED.swap(true, Ordering::SeqCst) {
+        return
+    }
+    // the exiting thread's thread_local destructors first, while everything they might use is still constructed
+    tls::run_main_thread_dtors();
+    // like glibc, we only hold the lock long enough to work out what to run; a destructor is free to dlopen or dlsym, and so is any other thread still running
+    let finis = {
+        let _guard = lock::LINK_LOCK.lock();
+        if unsafe { LINKER.is_null() } {
+            return
+        }
+        let linker = unsafe { &*LINKER };
+        linker.initialized.iter().rev().map(|&i| Fini::new(&linker.link_map[i])).collect::<Vec<Fini>>()
+    };
+    for fini in finis {
+        dbg!(LIBS, "{}: fini_array: 0x{:x} ({} entries) fini: 0x{:x}", fini.name, fini.fini_array, fini.count, fini.fini);
+        let start = trace::start();
+        fini.run();
+        trace::span("fini", &fini.name, start);
+    }
+    trace::flush();
+}
+
+/// An object's destructors, copied out of the link map so they can run without holding the link lock
+struct Fini {
+    name: String,
+    fini_array: usize,
+    count: usize,
+    fini: usize,
+}
+
+impl Fini {
+    fn new (so: &SharedObject) -> Fini {
+        Fini {
+            name: so.name().to_string(),
+            fini_array: so.link_info.fini_array as usize,
+            count: so.link_info.fini_arraysz as usize / mem::size_of::<usize>(),
+            fini: so.link_info.fini as usize,
+        }
+    }
+
+    fn run (&self) {
+        type FiniFn = extern fn ();
+        let array = self.fini_array as *const usize;
+        for i in (0..self.count).rev() {
+            unsafe {
+                let f = *array.offset(i as isize);
+                if f != 0 && f != !0 {
+                    mem::transmute::<usize, FiniFn>(f)();
+                }
+            }
+        }
+        if self.fini != 0 {
+            unsafe { mem::transmute::<usize, FiniFn>(self.fini)(); }
+        }
+    }
+}
+
+/// What `dlopen` does with the new object's RELRO once it's relocated; see `loader::serialize_relro` and `loader::map_relro`
+#[derive(Debug, Clone, Copy)]
+pub enum RelroSharing {
+    Private,
+    /// Write it to this file
+    Write (RawFd),
+    /// Share the pages of a previous `Write` to this file
+    Use (RawFd),
 }
-*/
+
+/// The linker once `link` has relocated everything, published before any constructor runs; the entry points programs call back into, like dlopen (which, unlike the PLT, have no GOT[1] rendezvous to go on) find the link map through this
+pub static mut LINKER: *const Linker<'static> = 0 as *const Linker<'static>;
 
 /// The dynamic linker
 /// TODO: Change permissions on most of these fields
@@ -125,9 +358,22 @@ pub struct Linker<'process> {
     config: Config<'process>,
     working_set: Box<HashMap<String, SharedObject<'process>>>, // TODO: we can eventually drop this or have it stack local var instead of field
     link_map_order: Vec<String>,
-    link_map: Vec<SharedObject<'process>>,
+    missing: Vec<String>, // when tracing, the objects we couldn't find, which ldd reports rather than failing on
+    aliases: HashMap<String, String>, // other names objects have been asked for by, e.g., a symlink or an explicit path, to the name we loaded them under
+    // boxed, so GOT[1] rendezvous can keep pointing at it even as `dlopen` grows it, and after we leak ourselves into `LINKER`
+    link_map: Box<Vec<SharedObject<'process>>>,
+    indices: HashMap<String, usize>, // every name an object in the link map was loaded by, and its soname, to its index
+    // boxed for the same reason as the link map
+    scopes: Box<Scopes>,
+    initialized: Vec<usize>, // link map indices in the order we ran their constructors; `_dryad_fini` runs destructors in reverse
     gdb: &'process mut gdb::Debug,
     lachesis: tls::Lachesis, // our tls delegate
+    trampoline: usize, // what goes in every GOT[2]; see `runtime::select_trampoline`
+    init_args: (isize, *const *const u8, *const *const u8), // argc, argv and envp, which every constructor gets, including those run by dlopen long after we've returned
+    started: u64, // when we got going, for `LD_DEBUG=statistics`
+    timings: stats::Timings,
+    stats: Vec<stats::Object>, // by link map index
+    bound: Vec<scope::Bound>, // by link map index, what its relocations bound to, for `LD_DEBUG=unused` and `LD_DRYAD_DUMP`
 }
 
 impl<'process> fmt::Debug for Linker<'process> {
@@ -146,6 +392,7 @@ impl<'process> fmt::Debug for Linker<'process> {
 impl<'process> Linker<'process> {
     pub fn new<'kernel> (base: usize, block: &'kernel kernel_block::KernelBlock) -> Result<Linker<'kernel>, &'static str> {
         unsafe {
+            let started = stats::now();
 
             let ehdr = &*(base as *const Header);
             let addr = (base + ehdr.e_phoff as usize) as *const program_header::ProgramHeader;
@@ -154,12 +401,14 @@ impl<'process> Linker<'process> {
             if let Some(dynamic) = dyn::from_phdrs(load_bias, &phdrs) {
                 let info = dyn::DynamicInfo::new(&dynamic, load_bias);
                 relocation::relocate_linker(load_bias, &info, &phdrs);
+                let relocated = stats::now();
                 // dryad has successfully relocated itself; time to init tls
                 let mut auxv = auxv::from_raw(block.auxv);
                 auxv[auxv::AT_PHDR] = addr as usize;
                 // again, one day we'll init lachesis and tls for the duration of dryad relocation+linking, using our custom hand-rolled TLS impl
                 // tls::Lachesis::init_from_phdrs(load_bias as usize, phdrs);
                 tls::__init_tls(auxv.as_ptr()); // this _should_ be safe since vec only allocates and shouldn't access tls. maybe.
+                let timings = stats::Timings { self_relocation: relocated - started, tls: stats::now() - relocated, .. stats::Timings::default() };
 
                 // we relocated ourselves so it should be safe to init the gdb debug protocols, use global data, reference static strings, call sweet functions, etc.
                 utils::set_panic(); // set this as early as we can
@@ -169,19 +418,31 @@ impl<'process> Linker<'process> {
                 gdb.relocated_init(base);
 
                 let config = Config::new(&block);
-                let debug = config.debug;
-                dbg!(debug, "init dryad with load_bias: 0x{:x}", load_bias);
+                debug::init(config.debug, config.debug_output);
+                if let Some(path) = config.trace {
+                    trace::init(path);
+                    // we couldn't trace these as they happened, but we did time them
+                    trace::record("startup", "self relocation", started, relocated);
+                    trace::record("startup", "tls", relocated, relocated + timings.tls);
+                }
+                dbg!(FILES, "init dryad with load_bias: 0x{:x}", load_bias);
+                let trampoline = runtime::select_trampoline();
                 let mut working_set = Box::new(HashMap::new());
                 let mut link_map_order = Vec::new();
-                let link_map = Vec::new();
+                let link_map = Box::new(Vec::new());
 
                 //let soname = utils::str_at(soname, 0);
 
                 if let Some(vdso_addr) = block.getauxval(auxv::AT_SYSINFO_EHDR) {
-                    let vdso = SharedObject::from_raw(vdso_addr);
-                    dbg!(config.debug, "loaded vdso {} at 0x{:x}", vdso.name(), vdso.load_bias);
-                    link_map_order.push(vdso.name().to_string());
-                    working_set.insert(vdso.name().to_string(), vdso);
+                    // the vdso is purely an optimization, so if the kernel hands us something we can't make sense of, we just go without
+                    match SharedObject::from_raw(vdso_addr) {
+                        Ok (vdso) => {
+                            dbg!(FILES, "loaded vdso {} at 0x{:x}", vdso.name(), vdso.load_bias);
+                            link_map_order.push(vdso.name().to_string());
+                            working_set.insert(vdso.name().to_string(), vdso);
+                        },
+                        Err (e) => dbg!(FILES, "ignoring vdso at 0x{:x}: {}", vdso_addr, e),
+                    }
                 };
 
                 Ok (Linker {
@@ -193,10 +454,21 @@ impl<'process> Linker<'process> {
                     config: config,
                     working_set: working_set,
                     link_map_order: link_map_order,
+                    missing: Vec::new(),
+                    aliases: HashMap::new(),
                     link_map: link_map,
+                    indices: HashMap::new(),
+                    scopes: Box::new(Scopes::default()),
+                    initialized: Vec::new(),
                     auxv: auxv,
                     gdb: gdb,
-                    lachesis: tls::Lachesis::new(debug),
+                    lachesis: tls::Lachesis::new(),
+                    trampoline: trampoline,
+                    init_args: (block.argc, block.argv.as_ptr(), block.env.as_ptr()),
+                    started: started,
+                    timings: timings,
+                    stats: Vec::new(),
+                    bound: Vec::new(),
                 })
 
             } else {
@@ -206,17 +478,518 @@ impl<'process> Linker<'process> {
         }
     }
 
-    /// Maybe returns the symbol which matches the name, and the SharedObject in which was found
-    fn find_symbol(&self, name: &str) -> Option<(&sym::Sym, &SharedObject)> {
-        // actually, this is an unfair optimization; the library might use a different hash system, like sysv
-        // in which case we can't pre-hash using gnu_hash, unless we assume every lib uses gnu_hash :/
+    /// For when we're run as `dryad.so.1 ./program args...`: maps the program like the kernel would have, patches the auxv to describe it instead of us,
+    /// and drops our own arguments from the stack, so from here on everything (including the program itself) sees exactly what it would have if the kernel had run us as its interpreter.
+    /// Returns the program's entry point.
+    ///
+    /// `block` is the kernel block made from `raw_args`, and is stale once this succeeds; make a new one.
+    pub unsafe fn exec(&mut self, raw_args: *mut usize, block: &kernel_block::KernelBlock) -> Result<usize, String> {
+        let args: Vec<&'process str> = block.argv[1..].iter().map(|arg| utils::str_at(*arg, 0)).collect();
+        // our name, and our options
+        let skip = match self.config.parse_args(&args)? {
+            Invocation::Run (idx) => idx + 1,
+            Invocation::Help => {
+                utils::write(USAGE);
+                utils::_exit(0);
+                return Err ("Error: exit failed".to_string())
+            },
+        };
+        for module in &self.config.audit {
+            utils::write_err(&format!("dryad: warning: auditing is not supported, ignoring {}\n", module));
+        }
+        let program = utils::str_at(block.argv[skip], 0);
+        let file = File::open(program).map_err(|e| format!("Error: cannot open {}: {}", program, e))?;
+        let source = loader::Source::from_file(&file);
+        if self.config.verify {
+            // like ld.so: 0 if it's a dynamic program we could run, 1 if it's static, and 2 if it's anything else
+            let code = match loader::verify(&source, true) {
+                Ok ((_, phdrs)) => if phdrs.iter().any(|phdr| phdr.p_type == program_header::PT_DYNAMIC) { 0 } else { 1 },
+                Err (_) => 2,
+            };
+            utils::_exit(code);
+        }
+        let loading = stats::now();
+        let (phdr, phnum, entry) = loader::load_executable(program, &source)?;
+        trace::record("startup", "load executable", loading, stats::now());
+        self.timings.loading += stats::now() - loading;
+        dbg!(FILES, "running {} with AT_PHDR: 0x{:x} AT_PHNUM: {} AT_ENTRY: 0x{:x}", program, phdr, phnum, entry);
+
+        block.setauxval(auxv::AT_PHDR, phdr);
+        block.setauxval(auxv::AT_PHNUM, phnum);
+        block.setauxval(auxv::AT_ENTRY, entry);
+        block.setauxval(auxv::AT_BASE, self.base);
+        block.setauxval(auxv::AT_EXECFN, block.argv[skip] as usize);
+        if let Some(argv0) = self.config.argv0 {
+            // it's one of our arguments, so it's already nul terminated, and it stays put when we skip them
+            *(block.argv.as_ptr().offset(skip as isize) as *mut *const u8) = argv0.as_ptr();
+        }
+        block.skip_args(raw_args, skip);
+
+        // argv and the auxv just moved, so anything we kept from the old block has to be taken again
+        let block = kernel_block::KernelBlock::new(raw_args);
+        self.auxv = auxv::from_raw(block.auxv);
+        self.init_args = (block.argc, block.argv.as_ptr(), block.env.as_ptr());
+        Ok (entry)
+    }
+
+    /// Maybe returns the symbol which matches the name and version, and the SharedObject in which was found, searching the scopes of the object at `idx`
+    fn find_symbol(&self, idx: usize, name: &str, version: Option<&Version>) -> Option<(&sym::Sym, &SharedObject)> {
+        self.scopes.lookup(&self.link_map, idx, name, version)
+    }
+
+    /// `find_symbol` for a relocation in the object at `idx`, noting the provider (and when dumping, the binding itself) in `bound`, the object's record of what it binds to
+    fn bind_symbol(&self, idx: usize, name: &str, version: Option<&Version>, bound: &mut scope::Bound) -> Option<(&sym::Sym, &SharedObject)> {
+        self.scopes.lookup_index(&self.link_map, idx, name, version).map(|(symbol, i)| {
+            if !bound.providers.contains(&i) {
+                bound.providers.push(i);
+            }
+            if self.config.dump.is_some() {
+                bound.bindings.push(scope::Binding {
+                    symbol: name.to_string(),
+                    version: version.map(|version| version.name.to_string()),
+                    provider: i,
+                    addr: symbol.st_value as usize + self.link_map[i].load_bias,
+                });
+            }
+            (symbol, &self.link_map[i])
+        })
+    }
+
+    /// Hands libc our fork handlers, once it's up (tls included); a child forked while another thread held the link lock would otherwise deadlock.
+    /// glibc's `pthread_atfork` lives in libc_nonshared.a, i.e., in the program itself if it uses it at all, so we go through what that calls, `__register_atfork`;
+    /// musl just exports `pthread_atfork`
+    fn register_atfork(&self) {
+        type RegisterAtforkFn = extern fn (prepare: extern fn (), parent: extern fn (), child: extern fn (), dso_handle: *const libc::c_void) -> libc::c_int;
+        type AtforkFn = extern fn (prepare: extern fn (), parent: extern fn (), child: extern fn ()) -> libc::c_int;
+        let res = if let Some((symbol, so)) = self.find_symbol(0, "__register_atfork", None) {
+            dbg!(LIBS, "registering fork handlers with __register_atfork in {}", so.name());
+            let register_atfork = unsafe { mem::transmute::<usize, RegisterAtforkFn>(symbol.st_value as usize + so.load_bias) };
+            // no dso handle, since we're never unloaded
+            register_atfork(lock::_dryad_atfork_prepare, lock::_dryad_atfork_parent, lock::_dryad_atfork_child, ptr::null())
+        } else if let Some((symbol, so)) = self.find_symbol(0, "pthread_atfork", None) {
+            dbg!(LIBS, "registering fork handlers with pthread_atfork in {}", so.name());
+            let pthread_atfork = unsafe { mem::transmute::<usize, AtforkFn>(symbol.st_value as usize + so.load_bias) };
+            pthread_atfork(lock::_dryad_atfork_prepare, lock::_dryad_atfork_parent, lock::_dryad_atfork_child)
+        } else {
+            dbg!(LIBS, "no __register_atfork or pthread_atfork in scope; fork handlers not registered, a fork while another thread holds the link lock will deadlock the child");
+            return
+        };
+        if res != 0 {
+            dbg!(LIBS, "registering fork handlers failed with {}; a fork while another thread holds the link lock will deadlock the child", res);
+        }
+    }
+
+    /// Every object we've loaded, in load order
+    pub fn link_map(&self) -> &[SharedObject<'process>] {
+        &self.link_map
+    }
+
+    /// The object whose segments `addr` is in, if any, e.g., the one a `__dso_handle` belongs to
+    pub fn object_containing(&self, addr: usize) -> Option<&SharedObject<'process>> {
+        self.link_map.iter().find(|so| so.contains(addr))
+    }
+
+    /// How long starting up took, and what relocating each object cost, by link map index; see `stats::dryad_get_stats`
+    pub fn stats(&self) -> (&stats::Timings, &[stats::Object]) {
+        (&self.timings, &self.stats)
+    }
+
+    /// The link map index of the object called `name`, whether that's its soname, the name it was loaded by, or an alias
+    fn index_of(&self, name: &str) -> Option<usize> {
+        let name = self.aliases.get(name).map(|name| name.as_str()).unwrap_or(name);
+        self.indices.get(name).cloned()
+    }
+
+    /// Whether an object with this soname is already in the link map, e.g., a dlopen'd object needing libc
+    fn is_loaded(&self, soname: &str) -> bool {
+        self.index_of(soname).is_some()
+    }
+
+    /// Appends `so`, loaded as `name`, to the link map
+    fn push_object(&mut self, name: &str, so: SharedObject<'process>) {
+        let idx = self.link_map.len();
+        self.indices.entry(name.to_string()).or_insert(idx);
+        if !so.name().is_empty() {
+            self.indices.entry(so.name().to_string()).or_insert(idx);
+        }
+        self.link_map.push(so);
+        self.stats.push(stats::Object::new(name));
+        self.bound.push(scope::Bound::default());
+    }
+
+    /// For each object in the link map, the link map indices of its `DT_NEEDED`s, in order
+    fn dependency_graph(&self) -> Vec<Vec<usize>> {
+        self.link_map.iter().map(|so| {
+            so.libs.iter().filter_map(|lib| self.index_of(lib)).collect()
+        }).collect()
+    }
+
+    /// The name of the object already loaded (or being loaded) from the file `id`, if any
+    fn find_by_file(&self, id: &image::FileId) -> Option<String> {
+        for (name, so) in self.working_set.iter() {
+            if so.file_id.as_ref() == Some (id) {
+                return Some (name.to_string())
+            }
+        }
+        // by a name it's in `indices` under, since it needn't have a soname
+        let idx = match self.link_map.iter().position(|so| so.file_id.as_ref() == Some (id)) { Some (idx) => idx, None => return None };
+        self.indices.iter().find(|&(_, &i)| i == idx).map(|(name, _)| name.to_string())
+    }
+
+    /// ldd, i.e., `LD_TRACE_LOADED_OBJECTS` or `--list`: prints every object we loaded, in the order we loaded them, and where, exactly like ld.so does,
+    /// so anything which parses ldd's output can parse ours, e.g.,
+    /// ```
+    ///         linux-vdso.so.1 (0x00007ffc2b5f1000)
+    ///         libm.so.6 => /usr/lib/libm.so.6 (0x00007f1b0c3a2000)
+    ///         libfoo.so => not found
+    ///         /tmp/dryad.so.1 (0x00007f1b0c7b0000)
+    /// ```
+    fn trace_loaded_objects(&self) {
+        let width = mem::size_of::<usize>() * 2;
+        let start = |so: &SharedObject| if so.map_begin != 0 { so.map_begin } else { so.load_bias };
+        // the interpreter is whatever the program asked for, even if it got us by `dryad.so.1 --list ./program`
+        let exe = &self.link_map[0];
+        let interp = exe.phdrs.iter().find(|phdr| phdr.p_type == program_header::PT_INTERP)
+            .map(|phdr| utils::str_at((phdr.p_vaddr as usize).wrapping_add(exe.load_bias) as *const u8, 0))
+            .unwrap_or("dryad.so.1");
+
+        if self.config.trace_prelinking {
+            // prelink wants everything, the program too, with both its mapping and its load bias, and its tls module and offset if it has one
+            for so in self.link_map.iter() {
+                let path = if self.is_dryad(so) { interp } else { so.path() };
+                let tls = match so.tls {
+                    Some (ref tls) => format!(" TLS(0x{:x}, 0x{:0width$x})", tls.modid, tls.offset as usize, width = width),
+                    None => "".to_string(),
+                };
+                utils::write(&format!("\t{} => {} (0x{:0width$x}, 0x{:0width$x}){}\n", so.name(), path, start(so), so.load_bias, tls, width = width));
+            }
+            return
+        }
+
+        for soname in &self.link_map_order {
+            if self.missing.contains(soname) {
+                utils::write(&format!("\t{} => not found\n", soname));
+                continue
+            }
+            let so = match self.index_of(soname) {
+                Some (idx) => &self.link_map[idx],
+                None => continue,
+            };
+            if soname == so.path() {
+                // e.g., the vdso, which isn't a file, or something asked for by path
+                utils::write(&format!("\t{} (0x{:0width$x})\n", soname, start(so), width = width));
+            } else {
+                utils::write(&format!("\t{} => {} (0x{:0width$x})\n", soname, so.path(), start(so), width = width));
+            }
+        }
+        utils::write(&format!("\t{} (0x{:0width$x})\n", interp, self.base, width = width));
+    }
+
+    /// ldd -d and -r: `name` is undefined in `so`, and whoever asked us to trace wants to hear about it
+    fn warn_undefined(&self, so: &SharedObject, name: &str) {
+        utils::write_err(&format!("undefined symbol: {}\t({})\n", name, so.path()));
+    }
+
+    /// `LD_DEBUG=unused`: reports, for every object from link map index `first` on, the `DT_NEEDED`s which satisfied none of its relocations.
+    /// When tracing, i.e., `ldd -u`, that's all we print, in ldd's format, and like ld.so we exit 1 if there were any.
+    /// Otherwise nothing was bound eagerly, so a dependency only used through lazy `JUMP_SLOT`s shows up here too, and so does one only used for tls or a copy relocation (see `scope::Bound`).
+    fn report_unused(&self, deps: &[Vec<usize>], first: usize) {
+        if !debug::enabled(debug::UNUSED) {
+            return
+        }
+        let unused: Vec<(usize, usize)> = scope::unused(deps, &self.bound).into_iter().filter(|&(i, _)| i >= first).collect();
+        if self.config.trace_loaded_objects {
+            if !unused.is_empty() {
+                utils::write("Unused direct dependencies:\n");
+            }
+            for &(requester, dep) in &unused {
+                if requester == 0 {
+                    utils::write(&format!("\t{}\n", self.link_map[dep].path()));
+                } else {
+                    // ld.so only reports the program's; we know about everyone's
+                    utils::write(&format!("\t{} (needed by {})\n", self.link_map[dep].path(), self.link_map[requester].path()));
+                }
+            }
+            utils::_exit(if unused.is_empty() { 0 } else { 1 });
+        }
+        for &(requester, dep) in &unused {
+            dbg!(UNUSED, "{}: unused dependency {}", self.link_map[requester].path(), self.link_map[dep].path());
+        }
+    }
+
+    /// Whether `so` is us
+    fn is_dryad(&self, so: &SharedObject) -> bool {
+        so.load_bias == self.load_bias
+    }
+
+    /// Runs the constructors of the object at `idx`: `DT_INIT` if it has one, and then everything in `DT_INIT_ARRAY`
+    fn init(&mut self, idx: usize) {
+        type InitFn = fn (argc: isize, argv: *const *const u8, env: *const *const u8) -> ();
+        self.initialized.push(idx);
+        // copied out before any constructor runs, since one which dlopens grows the link map, and `so` would then dangle
+        let (name, init, init_array, init_arraysz) = {
+            let so = &self.link_map[idx];
+            (so.name().to_string(), so.link_info.init as usize, so.link_info.init_array as usize, so.link_info.init_arraysz as usize)
+        };
+        dbg!(LIBS, "{}: init: 0x{:x} init_array: 0x{:x} ({} bytes)", name, init, init_array, init_arraysz);
+        let (argc, argv, envp) = self.init_args;
+        let start = trace::start();
+        if init != 0 {
+            let init = unsafe { mem::transmute::<usize, InitFn>(init)};
+            init(argc, argv, envp);
+        }
+        self.run_array(init_array, init_arraysz / mem::size_of::<usize>());
+        trace::span("init", &name, start);
+    }
+
+    /// Runs the executable's `DT_PREINIT_ARRAY`, which must run before any other constructor; only executables have one
+    fn preinit(&self, exe: &SharedObject) {
+        let mut preinit_array = 0;
+        let mut count = 0;
+        for dyn in exe.dynamic {
+            match dyn.d_tag as u64 {
+                dyn::DT_PREINIT_ARRAY => preinit_array = (dyn.d_val as usize).wrapping_add(exe.load_bias),
+                dyn::DT_PREINIT_ARRAYSZ => count = dyn.d_val as usize / mem::size_of::<usize>(),
+                _ => ()
+            }
+        }
+        if preinit_array != 0 {
+            // likewise, `exe` lives in the link map, which a preinit function that dlopens grows
+            let name = exe.name().to_string();
+            dbg!(LIBS, "{}: preinit_array: 0x{:x} ({} entries)", name, preinit_array, count);
+            let start = trace::start();
+            self.run_array(preinit_array, count);
+            trace::span("preinit", &name, start);
+        }
+    }
+
+    /// Calls each of the `count` function pointers at `array` in order, skipping the 0 and -1 entries old toolchains pad `.ctors`-style arrays with
+    fn run_array(&self, array: usize, count: usize) {
+        type InitFn = fn (argc: isize, argv: *const *const u8, env: *const *const u8) -> ();
+        let (argc, argv, envp) = self.init_args;
+        let array = array as *const usize;
+        for i in 0..count {
+            unsafe {
+                let f = *array.offset(i as isize);
+                if f != 0 && f != !0 {
+                    let init = mem::transmute::<usize, InitFn>(f);
+                    init(argc, argv, envp);
+                }
+            }
+        }
+    }
+
+    /// Opens the library `name` like ld.so does: a name with a slash in it is a path (relative to the cwd if it doesn't start with one), and anything else we search for.
+    /// Returns the absolute path we opened, so gdb and friends can find the file no matter where they were started from, along with the file and its verified headers, ready for `loader::load`.
+    pub fn open(&self, name: &str) -> Result<(PathBuf, File, loader::Verified), String> {
+        let (path, fd, verified) = if name.contains('/') {
+            let fd = File::open(name).map_err(|e| format!("Error: cannot open shared object file {}: {}", name, e))?;
+            let verified = loader::verify(&loader::Source::from_file(&fd), false)?;
+            (PathBuf::from(name), fd, verified)
+        } else {
+            self.search(name)?
+        };
+        if path.is_absolute() {
+            Ok ((path, fd, verified))
+        } else {
+            // e.g., a relative DT_NEEDED, or `LD_LIBRARY_PATH=.`
+            let cwd = env::current_dir().map_err(|e| format!("Error: cannot get the current directory for {}: {}", name, e))?;
+            Ok ((cwd.join(path), fd, verified))
+        }
+    }
+
+    /// Searches the library path for the first compatible file called `soname`, returning its path, the opened file, and the headers which made it compatible
+    pub fn search(&self, soname: &str) -> Result<(PathBuf, File, loader::Verified), String> {
+        // TODO: properly open the file using soname -> path with something like `resolve_soname`
+        // the last incompatible file we skipped over, if any, so a failed search can say why
+        let mut skipped = None;
+        for path in self.config.library_path.iter() {
+            let file = Path::new(path).join(soname);
+            if let Ok (fd) = File::open(&file) {
+                dbg!(LIBS, "opened: {:?}", fd);
+                // like ld.so, a wrong class/machine/type or otherwise broken file in one directory doesn't stop the search, e.g., a 32-bit lib in a multilib path
+                let verified = loader::verify(&loader::Source::from_file(&fd), false);
+                match verified {
+                    Ok (verified) => return Ok ((file, fd, verified)),
+                    Err (e) => {
+                        dbg!(LIBS, "skipping incompatible {:?}: {}", file, e);
+                        skipped = Some ((file, e));
+                    }
+                }
+            }
+        }
+        match skipped {
+            Some ((file, e)) => Err(format!("Error: could not find a compatible {} in {:?}; skipped {}: {}", &soname, self.config.library_path, file.display(), e.trim_left_matches("Error: "))),
+            None => Err(format!("Error: could not find {} in {:?}", &soname, self.config.library_path)),
+        }
+    }
+
+    /// Loads the object called `name` from `source` along with any of its dependencies not already loaded, relocates them, and runs their constructors;
+    /// returns the object's index in the link map.  If `name` (or the file `source` is) is already loaded, we just return it,
+    /// unless `placement` or `relro` asked for something we can't do to an object which is already mapped, which is an error.
+    ///
+    /// `flags` are dlopen's: `RTLD_NOW` binds everything up front, and `RTLD_GLOBAL` adds the object and its dependencies to the global scope,
+    /// otherwise (`RTLD_LOCAL`) only the object and its dependencies can see them.
+    ///
+    /// If anything fails, everything this loaded is unmapped and forgotten again (see `unwind`), so a failed dlopen leaves no trace, in the link map or in gdb's.
+    ///
+    /// `verified` is what `loader::verify` made of `source`.
+    ///
+    /// The caller must hold the link lock.
+    pub fn dlopen(&mut self, name: &str, source: &loader::Source, verified: loader::Verified, flags: libc::c_int, placement: loader::Placement, relro: RelroSharing) -> Result<usize, String> {
+        let loaded = self.link_map.iter().position(|so| so.path() == name)
+            .or_else(|| source.file_id().and_then(|id| self.link_map.iter().position(|so| so.file_id == Some (id))));
+        if let Some(idx) = loaded {
+            // it's mapped and relocated already, so there's no putting it somewhere else, and no relro left to write or share
+            match (&placement, &relro) {
+                (&loader::Placement::Anywhere, &RelroSharing::Private) | (&loader::Placement::Reserved { hint: true, .. }, &RelroSharing::Private) => (),
+                _ => return Err (format!("Error: {} is already loaded as {}, so its dlextinfo address and relro flags can't be honoured", name, self.link_map[idx].path())),
+            }
+            self.promote(idx, flags);
+            return Ok (idx)
+        }
+
+        // TODO: we'd need to allocate dynamic tls blocks and grow the dtv of every thread for these
+        if verified.1.iter().any(|phdr| phdr.p_type == program_header::PT_TLS) {
+            return Err (format!("Error: cannot dlopen {}: thread local storage in dlopen'd objects is not supported yet", name))
+        }
+
+        // everything a failure from here on has to put back, see `unwind`
+        let first = self.link_map.len();
+        let order_start = self.link_map_order.len();
+        let aliases = self.aliases.clone();
+        let modules = (self.lachesis.modules.len(), self.lachesis.current_modid);
+
+        let so = loader::load(name, name.to_string(), source, verified, placement, &mut self.lachesis)?;
+        unsafe {
+            self.gdb.update(gdb::State::RT_ADD);
+            self.gdb.add_so(&so);
+        }
+        let libs = so.libs.to_owned();
+        // the object goes first, then its dependencies in breadth first order
+        self.push_object(name, so);
+
+        // the dependencies go through the same search as at startup, landing in the working set and the tail of `link_map_order`
+        if let Err (e) = self.load_breadth_first(&libs) {
+            self.unwind(first, placement, order_start, aliases, modules);
+            return Err (e)
+        }
+
+        // nothing can fail to load now, so the dependencies can go into the link map too
+        let order = self.link_map_order[order_start..].to_owned();
+        for soname in &order {
+            if let Some(so) = self.working_set.remove(soname) {
+                self.push_object(soname, so);
+            }
+        }
+        unsafe { self.gdb.update(gdb::State::RT_CONSISTENT); }
+
+        // the new objects' local scopes; nothing already loaded depends on them, so the old ones don't change
+        let deps = self.dependency_graph();
+        for i in first..self.link_map.len() {
+            self.scopes.local.push(scope::local_scope(i, &deps));
+        }
+        self.promote(first, flags);
+
+        let bind_now = flags & dlfcn::RTLD_NOW != 0;
+        let mut bound = mem::replace(&mut self.bound, Vec::new());
+        let mut relocated = Ok (());
+        for i in first..self.link_map.len() {
+            match self.relocate_got(i, &self.link_map[i], bind_now, &mut bound[i]) {
+                Ok (counts) => self.stats[i].counts.add(&counts),
+                Err (e) => { relocated = Err (e); break },
+            }
+        }
+        if relocated.is_ok() {
+            for i in first..self.link_map.len() {
+                match self.relocate_plt(i, &self.link_map[i], bind_now, &mut bound[i]) {
+                    Ok (counts) => self.stats[i].counts.add(&counts),
+                    Err (e) => { relocated = Err (e); break },
+                }
+            }
+        }
+        self.bound = bound;
+
+        // only the object itself, not its dependencies, like bionic
+        let relocated = relocated.and_then(|_| match relro {
+            RelroSharing::Private => Ok (()),
+            RelroSharing::Write (fd) => loader::serialize_relro(&self.link_map[first], fd),
+            RelroSharing::Use (fd) => loader::map_relro(&self.link_map[first], fd),
+        });
+        if let Err (e) = relocated {
+            self.unwind(first, placement, order_start, aliases, modules);
+            return Err (e)
+        }
+        self.report_unused(&deps, first);
+
+        // dependencies have to be constructed before whatever needs them; anything already loaded was constructed long ago
+        for i in scope::init_order(&[first], &deps) {
+            if i >= first {
+                self.init(i);
+            }
+        }
+        Ok (first)
+    }
+
+    /// Undoes a dlopen which failed part way: unmaps every object it loaded, i.e., everything in the link map from `first` on, and whatever's still in the working set,
+    /// and forgets every name, scope entry, alias and tls module they had, so nothing (gdb included) ever sees them again.
+    /// `placement` is where the dlopen'd object itself, the one at `first`, went; its dependencies went anywhere.
+    fn unwind(&mut self, first: usize, placement: loader::Placement, order_start: usize, aliases: HashMap<String, String>, modules: (usize, u32)) {
+        let loaded: Vec<SharedObject<'process>> = self.link_map.drain(first..).collect();
+        let pending: Vec<SharedObject<'process>> = self.working_set.drain().map(|(_, so)| so).collect();
+        unsafe {
+            self.gdb.update(gdb::State::RT_DELETE);
+            for (i, so) in loaded.iter().chain(pending.iter()).enumerate() {
+                self.gdb.remove_so(so);
+                // none of its code has run, so nothing should have registered a thread_local destructor in it; but if something did, leaking it beats a thread exiting into unmapped memory
+                if so.pinned() {
+                    dbg!(FILES, "not unmapping {} after a failed dlopen, it has thread_local destructors pending", so.path());
+                    continue
+                }
+                dbg!(FILES, "unloading {} after a failed dlopen", so.path());
+                loader::unload(so, if i == 0 { placement } else { loader::Placement::Anywhere });
+            }
+            self.gdb.update(gdb::State::RT_CONSISTENT);
+        }
+        self.indices.retain(|_, idx| *idx < first);
+        self.scopes.local.truncate(first);
+        self.scopes.global.retain(|&i| i < first);
+        self.stats.truncate(first);
+        self.bound.truncate(first);
+        self.link_map_order.truncate(order_start);
+        self.aliases = aliases;
+        self.lachesis.modules.truncate(modules.0);
+        self.lachesis.current_modid = modules.1;
+    }
+
+    /// With `RTLD_GLOBAL`, appends the object at `idx` and its dependencies to the global scope, if they aren't there already.
+    /// Like ld.so, there's no demoting an object back out of it.
+    fn promote(&mut self, idx: usize, flags: libc::c_int) {
+        if flags & dlfcn::RTLD_GLOBAL != 0 {
+            let scopes = &mut *self.scopes;
+            for &i in &scopes.local[idx] {
+                if !scopes.global.contains(&i) {
+                    dbg!(SCOPES, "adding {} to the global scope", self.link_map[i].name());
+                    scopes.global.push(i);
+                }
+            }
+        }
+    }
+
+    /// Looks up `name` in the object with the dlopen handle `handle` (see `dlfcn`), and then in its dependencies, breadth first
+    pub fn dlsym(&self, handle: usize, name: &str) -> Result<usize, String> {
+        let idx = match handle.checked_sub(1) {
+            Some (idx) if idx < self.link_map.len() => idx,
+            _ => return Err (format!("Error: invalid handle {:#x}", handle)),
+        };
         let hash = gnu_hash::hash(name);
-        for so in &self.link_map {
-            if let Some(sym) = so.find(name, hash) {
-                return Some ((sym, &so))
+        for &i in &self.scopes.local[idx] {
+            let provider = &self.link_map[i];
+            if let Some(sym) = provider.find_versioned(name, hash, None) {
+                return Ok (sym.st_value as usize + provider.load_bias)
             }
         }
-        None
+        Err (format!("Error: {}: undefined symbol: {}", self.link_map[idx].path(), name))
     }
 
     /// Following the steps below, the dynamic linker and the program "cooperate"
@@ -265,12 +1038,12 @@ impl<'process> Linker<'process> {
     fn prepare_got<'a> (&self, idx: usize, pltgot: *const usize, name: &'a str) {
 
         if pltgot.is_null() {
-            dbg!(self.config.debug, "empty pltgot for {}", name);
+            dbg!(RELOC, "empty pltgot for {}", name);
             return
         }
 
         let len = self.link_map.len();
-        let rndzv = Box::new(runtime::Rendezvous { idx: idx, debug: self.config.debug, link_map: self.link_map.as_slice() });
+        let rndzv = Box::new(runtime::Rendezvous { idx: idx, bind_not: self.config.bind_not, link_map: &*self.link_map, scopes: &*self.scopes });
 
         unsafe {
             // got[0] == the program's address of the _DYNAMIC array, equal to address of the PT_DYNAMIC.ph_vaddr + load_bias
@@ -280,32 +1053,23 @@ impl<'process> Linker<'process> {
             let third_entry = pltgot.offset(2) as *mut usize;
 
             *second_entry = Box::into_raw(rndzv);
-            *third_entry = runtime::_dryad_resolve_symbol as usize;
-            dbg!(self.config.debug, "finished got setup for {} GOT[1] = {:?} GOT[2] = {:#x}", name, *second_entry, *third_entry);
+            *third_entry = self.trampoline;
+            dbg!(RELOC, "finished got setup for {} GOT[1] = {:?} GOT[2] = {:#x}", name, *second_entry, *third_entry);
         }
 
     }
 
-    #[inline(always)]
-    fn find_provider(&self, sym: &sym::Sym, name: &str) -> Option<&SharedObject> {
-        if sym.st_info != sym::STB_LOCAL {
-            let hash = gnu_hash::hash(name);
-            for ref so in &self.link_map {
-                if let Some(sym) = so.find(name, hash) {
-                    return Some(so);
-                }
-            }
-        }
-        None
-    }
-
     // TODO: reloc::R_X86_64_GLOB_DAT => this is a symbol resolution and requires full link map data, and _cannot_ be done before everything is relocated
     // ditto TPOFF64...
-    fn relocate_got (&self, idx: usize, so: &SharedObject) {
+    /// Does the relative and symbolic relocations of the object at `idx`, and prepares its GOT for lazy binding.
+    /// With eager binding (`bind_now`, as for `relocate_plt`, or the object's own `-z now`), a `GLOB_DAT` nothing defines is an error, unless it's weak, when it's null
+    fn relocate_got (&self, idx: usize, so: &SharedObject, bind_now: bool, bound: &mut scope::Bound) -> Result<stats::Counts, String> {
+        let bind_now = bind_now || so.bind_now;
         let symtab = &so.symtab;
         let strtab = &so.strtab;
         let bias = so.load_bias;
-        let mut count = 0;
+        let mut counts = stats::Counts::default();
+        let start = trace::start();
         let tls = so.tls;
         if so.link_info.textrel {
             let res = utils::mmap::mprotect_phdrs(&so.phdrs, bias, utils::mmap::PROT_WRITE);
@@ -315,8 +1079,9 @@ impl<'process> Linker<'process> {
             let sym = reloc::r_sym(reloc.r_info); // index into the sym table
             let symbol = &symtab[sym as usize];
             let name = &strtab[symbol.st_name as usize];
+            let version = so.requested_version(sym as usize);
             let addr = (reloc.r_offset as usize + bias) as *mut usize;
-            //dbg!(self.config.debug, "reloc {:p} -> {:x}", addr, unsafe { *addr });
+            //dbg!(RELOC, "reloc {:p} -> {:x}", addr, unsafe { *addr });
             match typ {
                 // B + A
                 relocation::RELATIVE => {
@@ -327,40 +1092,58 @@ impl<'process> Linker<'process> {
 
                     // set the relocations address to the load bias + the addend
                     unsafe { *addr = (addend + bias as isize) as usize; }
-                    //dbg!(self.config.debug, "after reloc {:p} -> {:x}", addr, unsafe { *addr });
-                    count += 1;
+                    //dbg!(RELOC, "after reloc {:p} -> {:x}", addr, unsafe { *addr });
+                    counts.relative += 1;
                 },
                 // S
                 relocation::GLOB_DAT => {
                     // resolve symbol;
                     // 1. start with exe, then next in needed, then next until symbol found
                     // 2. use gnu_hash with symbol name to get sym info
-                    if let Some((symbol, so)) = self.find_symbol(name) {
+                    counts.lookups += 1;
+                    if let Some((symbol, so)) = self.bind_symbol(idx, name, version.as_ref(), bound) {
                         // TODO: add 32-bit relocation
                         #[cfg(target_pointer_width = "64")]
                         unsafe { *addr = symbol.st_value as usize + so.load_bias; }
-                        count += 1;
+                        counts.symbolic += 1;
+                    } else if sym::st_bind(symbol.st_info) == sym::STB_WEAK {
+                        unsafe { *addr = 0; }
+                    } else if self.config.trace_loaded_objects {
+                        // ldd never runs anything, so it's not an error; with -d or -r, report it, and carry on to find the rest
+                        if self.config.warn {
+                            self.warn_undefined(so, name);
+                        }
+                    } else if bind_now {
+                        return Err(format!("Error: {}: undefined symbol: {}", so.path(), name))
+                    } else {
+                        dbgc!(orange_bold: RELOC, "dryad.warning", "no resolution for {}", name);
                     }
                 },
                 // ========= Platform specific relocations go here =========
-                #[cfg(arch = "x86_64")]
+                #[cfg(target_arch = "x86_64")]
                 // (S + A) - offset
                 reloc::R_X86_64_TPOFF64 => {
-                    if let Some((symbol, providing_so)) = self.find_symbol(name) {
-                        let tls = providing_so.tls.expect(&format!("Error: symbol \"{}\" required in {}, but the providing so {} does not have a TLS program header", name, so.name(), providing_so.name()));
+                    counts.lookups += 1;
+                    if let Some((symbol, providing_so)) = self.bind_symbol(idx, name, version.as_ref(), bound) {
+                        // a bad object, not our bug, so it fails the link (or the dlopen) instead of taking the process down with it
+                        let tls = match providing_so.tls {
+                            Some (tls) => tls,
+                            None => return Err(format!("Error: symbol \"{}\" required in {}, but the providing so {} does not have a TLS program header", name, so.name(), providing_so.name())),
+                        };
                         // TODO: it should be the symbol value (= tls offset in that module) plus the addend + the tls offset into the dtv of that module; i don't think load bias is used at all here, as it will be a relative got load?
                         unsafe { *addr = (symbol.st_value as i64 + reloc.r_addend as i64 - tls.offset as i64) as usize; }
-                        dbgc!(purple_bold: self.config.debug, "tls", "bound {} \"{}\" required in {} to provider {} with address 0x{:x}", sym::get_type(symbol.st_info), name, so.name(), providing_so.name(), unsafe { *addr });
-                        count += 1;
+                        dbgc!(purple_bold: TLS, "tls", "bound {} \"{}\" required in {} to provider {} with address 0x{:x}", sym::get_type(symbol.st_info), name, so.name(), providing_so.name(), unsafe { *addr });
+                        counts.symbolic += 1;
                     }
                 },
-                #[cfg(arch = "x86_64")]
+                #[cfg(target_arch = "x86_64")]
                 // S + A
                 reloc::R_X86_64_64 => {
                     // TODO: this is inaccurate because find_symbol is inaccurate
-                    if let Some((symbol, so)) = self.find_symbol(name) {
+                    counts.lookups += 1;
+                    if let Some((symbol, so)) = self.bind_symbol(idx, name, version.as_ref(), bound) {
                         unsafe { *addr = (reloc.r_addend + symbol.st_value as i64 + so.load_bias as i64) as usize; }
-                        count += 1;
+                        counts.symbolic += 1;
                     }
                 },
                 // TODO: add erro checking
@@ -368,18 +1151,24 @@ impl<'process> Linker<'process> {
             }
         }
 
-        dbg!(self.config.debug, "relocated {} symbols in {}", count, &so.name());
+        dbg!(RELOC, "relocated {} symbols in {}", counts.relative + counts.symbolic, &so.name());
 
         self.prepare_got(idx, so.pltgot, &so.name());
+        trace::span("relocate_got", so.name(), start);
+        Ok (counts)
     }
 
-    /// TODO: add check for if SO has the DT_BIND_NOW, and also other flags...
-    fn relocate_plt (&self, so: &SharedObject) {
+    /// Resolves ifuncs, and binds every `JUMP_SLOT` if `bind_now` (`LD_BIND_NOW` at startup, `RTLD_NOW` for dlopen) is set, or the object itself was linked with `-z now`.
+    /// Either way, whoever asked expects the GOT to be complete before any code runs, so a missing non-weak symbol is an error, as it is in ld.so (unless we're only tracing, i.e., ldd).
+    /// Returns what it cost, ifuncs included; the caller wants to know how long they took separately.
+    fn relocate_plt (&self, idx: usize, so: &SharedObject, bind_now: bool, bound: &mut scope::Bound) -> Result<stats::Counts, String> {
 
         let symtab = &so.symtab;
         let strtab = &so.strtab;
         let bias = so.load_bias;
-        let mut count = 0;
+        let mut counts = stats::Counts::default();
+        let start = trace::start();
+        let bind_now = bind_now || so.bind_now;
 
         // x86-64 ABI, pg. 78:
         // > Much as the global offset table redirects position-independent address calculations
@@ -390,15 +1179,25 @@ impl<'process> Linker<'process> {
             let sym = reloc::r_sym(reloc.r_info); // index into the sym table
             let symbol = &symtab[sym as usize];
             let name = &strtab[symbol.st_name as usize];
+            let version = so.requested_version(sym as usize);
             let addr = (reloc.r_offset as usize + bias) as *mut usize;
-            //dbg!(self.config.debug, "reloc {:p} -> {:x}", addr, unsafe { *addr });
+            //dbg!(RELOC, "reloc {:p} -> {:x}", addr, unsafe { *addr });
             match typ {
-                relocation::JUMP_SLOT if self.config.bind_now => {
-                    if let Some((symbol, so)) = self.find_symbol(name) {
-                        unsafe { *addr = symbol.st_value as usize + so.load_bias; }
-                        count += 1;
+                relocation::JUMP_SLOT if bind_now => {
+                    counts.lookups += 1;
+                    if let Some((symbol, provider)) = self.bind_symbol(idx, name, version.as_ref(), bound) {
+                        unsafe { *addr = symbol.st_value as usize + provider.load_bias; }
+                        counts.symbolic += 1;
+                    } else if sym::st_bind(symbol.st_info) == sym::STB_WEAK {
+                        unsafe { *addr = 0; }
+                        counts.symbolic += 1;
+                    } else if self.config.trace_loaded_objects {
+                        // ldd never runs anything, so it's not an error; with -r, report it, and carry on to find the rest
+                        if self.config.warn {
+                            self.warn_undefined(so, name);
+                        }
                     } else {
-                        dbgc!(orange_bold: self.config.debug, "dryad.warning", "no resolution for {}", name);
+                        return Err(format!("Error: {}: undefined symbol: {}", so.path(), name))
                     }
                 },
                 // fun @ (B + A)()
@@ -409,77 +1208,78 @@ impl<'process> Linker<'process> {
                     let addend = unsafe { (*addr) } as isize ;
 
                     let ifunc_addr = addend + bias as isize;
-//                    dbg!(self.config.debug, "irelative: bias: {:#x} addend: {:#x} addr: {:#x}", bias, reloc.r_addend, addr);
+//                    dbg!(RELOC, "irelative: bias: {:#x} addend: {:#x} addr: {:#x}", bias, reloc.r_addend, addr);
+                    let start = stats::now();
                     unsafe {
                         let ifunc = mem::transmute::<usize, (fn() -> usize)>(ifunc_addr as usize);
                         *addr = ifunc() as usize;
-//                        dbg!(self.config.debug, "ifunc addr: 0x{:x}", *reloc);
+//                        dbg!(RELOC, "ifunc addr: 0x{:x}", *reloc);
                     }
-                    count += 1;
+                    counts.ifunc_time += stats::now() - start;
+                    counts.ifuncs += 1;
                 },
                 // TODO: add error checking
                 _ => ()
             }
         }
         if so.link_info.textrel {
-            let res = utils::mmap::mprotect_phdrs(&so.phdrs, bias, 0);
+            if !utils::mmap::restore_phdrs(&so.phdrs, bias) {
+                return Err(format!("Error: cannot restore segment protections after text relocations in {} with errno {}", so.path(), utils::get_errno()))
+            }
+        }
+        // nothing writes into this object's relro region from here on (the lazy PLT slots live in .got.plt, which is only inside relro when linked `-z now`)
+        if !so.protect_relro() {
+            return Err(format!("Error: cannot apply RELRO protection to {} with errno {}", so.path(), utils::get_errno()))
         }
-        dbg!(self.config.debug, "relocate plt: {} symbols for {}", count, so.name());
+        dbg!(RELOC, "relocate plt: {} symbols for {}", counts.symbolic + counts.ifuncs, so.name());
+        trace::span("relocate_plt", so.name(), start);
+        Ok (counts)
     }
 
-    /// TODO: rename to something like `load_all` to signify on return everything has loaded?
     /// So: load many -> join -> relocate many -> join -> relocate executable and transfer control
     /// 1. Open fd to shared object ✓ - TODO: parse and use /etc/ldconfig.cache
     /// 2. get program headers ✓
     /// 3. mmap PT_LOAD phdrs ✓
     /// 4. compute load bias and base ✓
     /// 5. get _DYNAMIC real address from the mmap'd segments ✓
-    /// 6a. create SharedObject from above ✓
-    /// 6b. relocate the SharedObject, including GLOB_DAT ✓ TODO: TLS shite
-    /// 6c. resolve function and PLT; for now, just act like LD_PRELOAD is set
-    /// 7. add `soname` => `SharedObject` entry in `linker.loaded` TODO: use better structure, resolve dependency chain
-    fn load(&mut self, soname: &str) -> Result<(), String> {
-        // TODO: properly open the file using soname -> path with something like `resolve_soname`
-        let paths = self.config.library_path.to_owned(); // TODO: so we compile, fix unnecessary alloc
-
-        // soname ∉ linker.loaded
-        if !self.working_set.contains_key(soname) {
-            let mut found = false;
-            for path in paths {
-                let file = Path::new(&path).join(soname);
-                match File::open(&file) {
-                    Ok (mut fd) => {
-                        found = true;
-                        dbg!(self.config.debug, "opened: {:?}", fd);
-                        let shared_object = try!(loader::load(soname, file.to_string_lossy().into_owned(), &mut fd,  self.config.debug, &mut self.lachesis));
-                        unsafe { self.gdb.add_so(&shared_object); }
-
-                        let libs = &shared_object.libs.to_owned(); // TODO: fix this unnecessary allocation, but we _must_ insert before iterating
-                        self.working_set.insert(soname.to_string(), shared_object);
-
-                        // breadth first addition, and unnecessary amount of searching but who cares for now
-                        // this also fixes the snappy dedup problem
-                        for lib in libs {
-                            let mut is_elem = false;
-                            for lib2 in &self.link_map_order {
-                                if lib2 == lib { is_elem = true; break }
-                            }
-                            if !is_elem { self.link_map_order.push(lib.to_string());}
-                        }
-
-                        for lib in libs {
-                            try!(self.load(lib));
-                        }
-                        break
-                    },
-                    _ => (),
-                }
+    /// 6. create SharedObject from above ✓
+    /// 7. add `soname` => `SharedObject` entry in the working set ✓
+    ///
+    /// Loads `roots` and everything they need, breadth first: all of `roots`, then all of their `DT_NEEDED`s, then all of theirs, and so on, skipping anything already loaded.
+    /// Each object's name is pushed onto `link_map_order` as it's loaded, so that comes out breadth first too.
+    fn load_breadth_first(&mut self, roots: &[&str]) -> Result<(), String> {
+        let mut queue: VecDeque<String> = roots.iter().map(|lib| lib.to_string()).collect();
+        while let Some(soname) = queue.pop_front() {
+            // soname ∉ linker.loaded
+            if self.working_set.contains_key(&soname) || self.is_loaded(&soname) || self.aliases.contains_key(&soname) || self.missing.contains(&soname) {
+                continue
             }
-            if !found {
-                return Err(format!("Error: could not find {} in {:?}", &soname, self.config.library_path))
+            let start = trace::start();
+            let (file, fd, verified) = match self.open(&soname) {
+                Ok (opened) => opened,
+                Err (_) if self.config.trace_loaded_objects => {
+                    // ldd says so and carries on; nothing will ever run
+                    self.link_map_order.push(soname.clone());
+                    self.missing.push(soname);
+                    continue
+                },
+                Err (e) => return Err (e),
+            };
+            trace::span("open", &soname, start);
+            let source = loader::Source::from_file(&fd);
+            // a different name for a file we already have, e.g., a symlink, or the same file by path; either way it's the same object, not a second copy with its own globals
+            if let Some(name) = source.file_id().and_then(|id| self.find_by_file(&id)) {
+                dbg!(FILES, "{} ({}) is already loaded as {}", soname, file.display(), name);
+                self.aliases.insert(soname, name);
+                continue
             }
-        }
+            let shared_object = loader::load(&soname, file.to_string_lossy().into_owned(), &source, verified, loader::Placement::Anywhere, &mut self.lachesis)?;
+            unsafe { self.gdb.add_so(&shared_object); }
 
+            queue.extend(shared_object.libs.iter().map(|lib| lib.to_string()));
+            self.link_map_order.push(soname.clone());
+            self.working_set.insert(soname, shared_object);
+        }
         Ok (())
     }
     
@@ -490,7 +1290,7 @@ impl<'process> Linker<'process> {
     /// 3. Finally, relocates the executable, and then transfers control
     pub fn link(mut self, block: &kernel_block::KernelBlock) -> Result<(), String> {
 
-//        dbg!(self.config.debug, "I am that I am:\n  {:#?}", &self);
+//        dbg!(FILES, "I am that I am:\n  {:#?}", &self);
         /*
         let array = [1, 2, 3];
         crossbeam::scope(|scope| {
@@ -503,28 +1303,31 @@ impl<'process> Linker<'process> {
         */
 
         // build executable
-        dbgc!(red: self.config.debug, "dryad", "loading executable");
+        let loading = stats::now();
+        dbgc!(red: FILES, "dryad", "loading executable");
         let name = utils::str_at(block.argv[0], 0);
         let phdr_addr = block.getauxval(auxv::AT_PHDR).unwrap();
         let phnum  = block.getauxval(auxv::AT_PHNUM).unwrap();
+        let start = trace::start();
         let image = try!(SharedObject::from_executable(name, phdr_addr, phnum, &mut self.lachesis));
+        trace::span("parse", name, start);
 
-        dbg!(self.config.debug, "Main Image:\n  {:#?}", &image);
+        dbg!(FILES, "Main Image:\n  {:#?}", &image);
 
         // 1. load all
 
         // TODO: transfer ownership of libs (or allocate) to the linker, so it can be parallelized
         // this is the only obvious candidate for parallelization, and it's dubious at best... but large binaries spend 20% of time loading and 80% on relocation
-        self.link_map_order.extend(image.libs.iter().map(|s| s.to_string()));
+        // preloads come straight after the executable, so they interpose on everything else
+        let preload = self.config.preload.clone();
+        let roots: Vec<&str> = preload.iter().chain(image.libs.iter()).cloned().collect();
         unsafe {
             // insert the _r_debug struct into the executables _DYNAMIC array
             // this is unsafe because we use pointers because I don't feel like changing every borrowed reference for the dynamic array to a mutable borrow for one single time for the whole program duration that the _DYNAMIC array ever gets mutated
             gdb::insert_r_debug(image.dynamic);
             self.gdb.update(gdb::State::RT_ADD);
         }
-        for lib in &image.libs {
-            try!(self.load(lib));
-        }
+        try!(self.load_breadth_first(&roots));
         unsafe {
             // we need to read-add dryad otherwise gdb likes to unload it for some reason i have yet to determine; this is a hack.  See:
             // https://github.com/m4b/dryad/issues/4
@@ -533,17 +1336,36 @@ impl<'process> Linker<'process> {
             self.gdb.update(gdb::State::RT_CONSISTENT);
         }
 
-        dbg!(self.config.debug, "link_map_order: {:#?}", self.link_map_order);
+        dbg!(LIBS, "link_map_order: {:#?}", self.link_map_order);
 
-        self.link_map.reserve_exact(self.link_map_order.len()+1);
-        self.link_map.push(image);
+        self.link_map.reserve_exact(self.link_map_order.len()+2);
+        self.push_object(name, image);
         // TODO: we should go in reverse order like glibc ?
-        for soname in &self.link_map_order {
-            let so = self.working_set.remove(soname).unwrap();
-            self.link_map.push(so);
+        let order = mem::replace(&mut self.link_map_order, Vec::new());
+        for soname in &order {
+            // aliases never made it into the working set
+            if let Some(so) = self.working_set.remove(soname) {
+                self.push_object(soname, so);
+            }
+        }
+        self.link_map_order = order;
+        // and ourselves last, like ld.so, so the program and its libraries can bind to whatever we export (see etc/dynamic-list.txt), e.g., `dryad_dlopen_ext`
+        match unsafe { SharedObject::from_mapped("dryad", self.load_bias, self.phdrs) } {
+            Ok (dryad) => self.push_object("dryad", dryad),
+            Err (e) => dbg!(FILES, "not adding dryad to the link map: {}", e),
+        }
+        trace::record("startup", "loading", loading, stats::now());
+        self.timings.loading += stats::now() - loading;
+        // ldd -u only wants to hear what's unused, and that has to wait until everything's relocated
+        if self.config.trace_loaded_objects && !debug::enabled(debug::UNUSED) {
+            self.trace_loaded_objects();
+            // ldd -d and -r want the relocations done, so they can report what's undefined; otherwise we're done
+            if !self.config.warn {
+                utils::_exit(0);
+            }
         }
-        dbg!(self.config.debug, "working set is drained: {}", self.working_set.len() == 0);
-        dbg!(self.config.debug, "link_map ptr: {:#?}, cap = len: {}", self.link_map.as_ptr(), self.link_map.capacity() == self.link_map.len());
+        dbg!(FILES, "working set is drained: {}", self.working_set.len() == 0);
+        dbg!(FILES, "link_map ptr: {:#?}, cap = len: {}", self.link_map.as_ptr(), self.link_map.capacity() == self.link_map.len());
         // <join>
         // 2. relocate all
         // TODO: after _all_ SharedObject have been loaded, it is safe to relocate if we stick to ELF symbol search rule of first search executable, then in each of DT_NEEDED in order, then deps of first DT_NEEDED, and if not found, then deps of second DT_NEEDED, etc., i.e., breadth-first search.  Why this is allowed to continue past the executable's _OWN_ dependency list is anyone's guess; a penchant for chaos perhaps?
@@ -563,19 +1385,68 @@ impl<'process> Linker<'process> {
         // |_
         //
         // is reduced to [exe, libfoo, libbar, libbaz, libderp, libslerp, libmerp]
+        //
+        // which is exactly what `scope::global_scope` computes; see there for the details, and the local scopes
+        let deps = self.dependency_graph();
+        let preloads: Vec<usize> = preload.iter().filter_map(|lib| self.index_of(lib)).collect();
+        let mut global = scope::global_scope(0, &preloads, &deps);
+        // whatever nobody needs, i.e., the vdso and ourselves, is still fair game, just last
+        for i in 0..self.link_map.len() {
+            if !global.contains(&i) {
+                global.push(i);
+            }
+        }
+        dbg!(SCOPES, "global scope: {:?}", global.iter().map(|&i| self.link_map[i].name()).collect::<Vec<_>>());
+        self.scopes.local = (0..self.link_map.len()).map(|i| scope::local_scope(i, &deps)).collect();
+        self.scopes.global = global;
 
         // TODO: determine ld-so's relocation order (_not_ equivalent to it's search order, which is breadth first from needed libs)
         // Because gnu_ifuncs essentially execute arbitrary code, including calling into the GOT, if the GOT isn't setup and relative relocations, for example, haven't been processed in the binary which has the reference, we're doomed.  Example is a libm ifunc (after matherr) for `__exp_finite` that calls `__get_cpu_features` which resides in libc.
 
-        for (i, so) in self.link_map.iter().enumerate() {
-            self.relocate_got(i, so);
-        }
-
+        // we relocated ourselves way back in `new`, and doing it twice would be a disaster
+        let mut counts = vec![stats::Counts::default(); self.link_map.len()];
+        let mut bound = mem::replace(&mut self.bound, Vec::new());
         // I believe we can parallelize the relocation pass by:
         // 1. skipping constructors, or blocking until the linkmaps deps are signalled as finished
         // 2. if skip, rerun through the link map again and call each constructor, since the GOT was prepared and now dynamic calls are ready
-        for so in self.link_map.iter() {
-            self.relocate_plt(so);
+        // whether a dependency is unused isn't known until everything is bound, lazy or not;
+        // but like ld.so, only `ldd -u` pays for that: a program merely run with LD_DEBUG=unused keeps its lazy binding (and its startup time),
+        // and gets told about whatever's only used through the plt too
+        let bind_now = self.config.bind_now || (debug::enabled(debug::UNUSED) && self.config.trace_loaded_objects);
+        let start = stats::now();
+        for (i, so) in self.link_map.iter().enumerate() {
+            if !self.is_dryad(so) {
+                counts[i] = self.relocate_got(i, so, bind_now, &mut bound[i])?;
+            }
+        }
+        trace::record("startup", "relocate_got", start, stats::now());
+        self.timings.relocate_got = stats::now() - start;
+
+        let start = stats::now();
+        for (i, so) in self.link_map.iter().enumerate().filter(|&(_, so)| !self.is_dryad(so)) {
+            counts[i].add(&self.relocate_plt(i, so, bind_now, &mut bound[i])?);
+        }
+        self.bound = bound;
+        let ifuncs: u64 = counts.iter().map(|counts| counts.ifunc_time).sum();
+        trace::record("startup", "relocate_plt", start, stats::now());
+        self.timings.relocate_plt = stats::now() - start - ifuncs;
+        self.timings.ifuncs = ifuncs;
+        for (object, counts) in self.stats.iter_mut().zip(counts.iter()) {
+            object.counts.add(counts);
+        }
+
+        self.report_unused(&deps, 0);
+
+        // whatever ldd asked for has been printed, and it never runs anything
+        if self.config.trace_loaded_objects {
+            utils::_exit(0);
+        }
+
+        // before any constructor gets the chance to dlopen something; and it's only what's bound so far, lazy binding isn't our business here
+        if let Some(path) = self.config.dump {
+            if let Err(e) = dump::write(path, &self.link_map, &deps, &self.scopes, &self.bound) {
+                utils::write_err(&format!("dryad: warning: {}\n", e.trim_left_matches("Error: ")));
+            }
         }
 
 //        println!("libc: {:#?}", unsafe { &::tls::__libc});
@@ -586,30 +1457,11 @@ impl<'process> Linker<'process> {
         // so the structures we setup don't segfault when we try to access them back again after passing through assembly to `dryad_resolve_symbol`,
         // which from the compiler's perspective means they needs to be dropped
         // "Blessed are the forgetful, for they get the better even of their blunders."
-        dbg!(self.config.debug, "\"Without forgetting it is quite impossible to live at all.\"");
+        dbg!(LIBS, "\"Without forgetting it is quite impossible to live at all.\"");
         if !self.config.secure && self.config.show_auxv {
             auxv::show(&self.auxv);
         }
 
-        type InitFn = fn (argc: isize, argv: *const *const u8, env: *const *const u8) -> ();
-        for so in self.link_map.iter() {
-//            dbg!(self.config.debug, "{}: init: 0x{:x} - 0x{:x} = 0x{:x}", so.name(), so.link_info.init, so.load_bias, so.link_info.init.wrapping_sub(so.load_bias));
-            let (argc, argv, envp) = (block.argc, block.argv.as_ptr(), block.env.as_ptr());
-            if so.link_info.init != 0 {
-                let init = unsafe { mem::transmute::<usize, InitFn>(so.link_info.init as usize)};
-                init(argc, argv, envp);
-                let init_arr = so.link_info.init_array as *mut InitFn;
-                let sz = so.link_info.init_arraysz as usize;
-                let count = sz / mem::size_of::<usize>();
-                for i in 0..count {
-                    unsafe {
-                        let init = mem::transmute::<usize, InitFn>(*init_arr.offset(i as isize) as usize);
-                        init(argc, argv, envp);
-                    }
-                }
-            }
-        }
-
         unsafe {
             // one day we will init_tls using lachesis - but it is not this day!
             // // ::tls::init_tls(self.lachesis.current_modid, &mut self.lachesis.modules);
@@ -617,10 +1469,37 @@ impl<'process> Linker<'process> {
             // tls::Lachesis::init_from_phdrs(self.load_bias as usize, self.phdrs);
 // calling this with the program header of the entry runs it as normal
 // except since libc isn't properly initialized (__libc_malloc_initialized == 0), it tries to load dynamically and crashes since none of the rtld_global struct is setup :/
+            let start = stats::now();
             let auxv = auxv::from_raw(block.auxv);
             tls::__init_tls(auxv.as_ptr());
+            self.timings.tls += stats::now() - start;
+        }
+        // everything from here on can be called from a constructor: a fork needs our handlers, and dlopen, dlsym and friends need the linker,
+        // so we leak ourselves into `LINKER` now, rather than forgetting ourselves outright, and construct everything through it, like dlopen does
+        self.register_atfork();
+        let linker = unsafe {
+            LINKER = Box::into_raw(Box::new(mem::transmute::<Linker<'process>, Linker<'static>>(self)));
+            &mut *(LINKER as *mut Linker<'static>)
+        };
+
+        // dependencies before dependents, after the executable's preinit_array; the executable's own constructors are crt1's job (`__libc_csu_init`), and we constructed ourselves
+        {
+            let _guard = lock::LINK_LOCK.lock();
+            let start = stats::now();
+            linker.preinit(&linker.link_map[0]);
+            let roots: Vec<usize> = (0..deps.len()).collect();
+            for i in scope::init_order(&roots, &deps) {
+                if i != 0 && !linker.is_dryad(&linker.link_map[i]) {
+                    linker.init(i);
+                }
+            }
+            trace::record("startup", "constructors", start, stats::now());
+            linker.timings.constructors = stats::now() - start;
+            linker.timings.total = stats::now() - linker.started;
+            if debug::enabled(debug::STATISTICS) {
+                stats::print(&linker.timings, &linker.stats);
+            }
         }
-        mem::forget(self);
         Ok (())
     }
 }
@@ -2,24 +2,25 @@
 /// TODO: fix the high address mapperr for `__libc_start_main`
 
 use std::fs::File;
-use std::os::unix::io::AsRawFd;
+use std::mem;
+use std::ptr;
+use std::slice;
+use std::os::unix::io::{AsRawFd, RawFd};
 use libc;
 
 use utils::{self, mmap, page};
-use image::SharedObject;
+use image::{self, FileId, SharedObject};
 use elf::header;
 use elf::program_header;
-use elf::dyn;
-use elf::sym;
-use elf::reloc;
-use goblin::strtab::Strtab;
-use elf::gnu_hash::GnuHash;
 use tls;
+use trace;
 
+/// Returns the page-aligned size, min_vaddr and max_vaddr spanned by the `PT_LOAD` segments, and the largest `p_align` among them
 #[inline(always)]
-fn compute_load_size (phdrs: &[program_header::ProgramHeader]) -> (usize, usize, usize) {
+fn compute_load_size (phdrs: &[program_header::ProgramHeader]) -> (usize, usize, usize, usize) {
     let mut max_vaddr = 0;
-    let mut min_vaddr = 0;
+    let mut min_vaddr = usize::max_value();
+    let mut max_align = page::PAGE_SIZE;
     for phdr in phdrs {
 
         if phdr.p_type != program_header::PT_LOAD {
@@ -35,81 +36,381 @@ fn compute_load_size (phdrs: &[program_header::ProgramHeader]) -> (usize, usize,
         if tmp > max_vaddr {
             max_vaddr = tmp;
         }
+
+        let align = phdr.p_align as usize;
+        if align > max_align && align.is_power_of_two() {
+            max_align = align;
+        }
+    }
+
+    if min_vaddr > max_vaddr {
+        // no PT_LOAD at all; `load` reports this properly
+        min_vaddr = 0;
     }
 
     min_vaddr = page::page_start(min_vaddr);
     max_vaddr = page::page_end(max_vaddr);
 
-    ((max_vaddr - min_vaddr), min_vaddr, max_vaddr)
+    ((max_vaddr - min_vaddr), min_vaddr, max_vaddr, max_align)
+}
+
+/// Where in the address space an object goes
+#[derive(Debug, Clone, Copy)]
+pub enum Placement {
+    /// Wherever the kernel likes, which is nearly always what you want
+    Anywhere,
+    /// Inside `[addr, addr + size)`, which the caller has already mapped (usually `PROT_NONE`) and which we map the segments over;
+    /// if the object doesn't fit, that's an error, unless `hint` is set, in which case we fall back to `Anywhere`
+    Reserved { addr: usize, size: usize, hint: bool },
+    /// At the object's own vaddrs, i.e., a load bias of 0, which is the only way to load an `ET_EXEC`; it's an error if something is already there
+    Fixed,
 }
 
 #[inline(always)]
-fn reserve_address_space (phdrs: &[program_header::ProgramHeader]) -> Result <(usize, usize, usize), String> {
+fn reserve_address_space (phdrs: &[program_header::ProgramHeader], placement: Placement) -> Result <(usize, usize, usize), String> {
 
-    let (size, min_vaddr, max_vaddr) = compute_load_size(&phdrs);
+    let (size, min_vaddr, max_vaddr, align) = compute_load_size(&phdrs);
+
+    if let Placement::Reserved { addr, size: reserved_size, hint } = placement {
+        if page::page_offset(addr) != 0 {
+            return Err (format!("Error: reserved address {:#x} is not page aligned", addr))
+        }
+        if size <= reserved_size {
+            // the caller's mapping is the reservation, so nothing to mmap; and we don't try to honour a large p_align, like bionic
+            return Ok ((addr, addr.wrapping_sub(min_vaddr), addr + size))
+        }
+        if !hint {
+            return Err (format!("Error: reserved address space of {:#x} bytes at {:#x} is too small, need {:#x}", reserved_size, addr, size))
+        }
+    }
+
+    if let Placement::Fixed = placement {
+        // no MAP_FIXED, since that would happily map over whatever's there already (e.g., us); the kernel takes the address as a hint, and we check it took it
+        let reserved = unsafe { mmap::mmap(min_vaddr as *const usize,
+                                           size,
+                                           mmap::PROT_NONE,
+                                           (mmap::MAP_PRIVATE | mmap::MAP_ANONYMOUS) as libc::c_int,
+                                           -1,
+                                           0) };
+        if reserved == mmap::MAP_FAILED {
+            return Err (format!("Error: anonymous mmap failed for size {:x} with errno {}", size, utils::get_errno()))
+        }
+        if reserved != min_vaddr {
+            unsafe { mmap::unmap(reserved as *const usize, size); }
+            return Err (format!("Error: cannot map {:#x} - {:#x}, something is already there", min_vaddr, max_vaddr))
+        }
+        return Ok ((min_vaddr, 0, max_vaddr))
+    }
+
+    // if a segment wants more than page alignment (e.g., 2MiB for transparent huge pages), over-reserve by the alignment,
+    // then trim either side so the load bias is a multiple of it
+    let reserve_size = if align > page::PAGE_SIZE { size + align } else { size };
 
     let mmap_flags = mmap::MAP_PRIVATE | mmap::MAP_ANONYMOUS;
-    let start = unsafe { mmap::mmap(0 as *const usize,
-                                    size,
-                                    // for now, using PROT_NONE seems to give SEGV_ACCERR on execution of PT_LOAD mmaped segments (i.e., operation not allowed on mapped object)
-                                    mmap::PROT_EXEC | mmap::PROT_READ | mmap::PROT_WRITE,
-                                    mmap_flags as libc::c_int,  // TODO: I think we should copy glibc's lead here and use their mmap flags
-                                    -1,
-                                    0) };
+    // nothing but the segments we map over this should ever be accessible, so PROT_NONE; the segments bring their own protections with them
+    let reserved = unsafe { mmap::mmap(0 as *const usize,
+                                       reserve_size,
+                                       mmap::PROT_NONE,
+                                       mmap_flags as libc::c_int,  // TODO: I think we should copy glibc's lead here and use their mmap flags
+                                       -1,
+                                       0) };
 
-    if start == mmap::MAP_FAILED {
+    if reserved == mmap::MAP_FAILED {
 
-        Err (format!("Error: anonymous mmap failed for size {:x} with errno {}", size, utils::get_errno()))
+        Err (format!("Error: anonymous mmap failed for size {:x} with errno {}", reserve_size, utils::get_errno()))
 
     } else {
 
-        let load_bias = start - min_vaddr;
+        let load_bias = (reserved.wrapping_sub(min_vaddr) + align - 1) & !(align - 1);
+        let start = load_bias.wrapping_add(min_vaddr);
         let end = start + size;
+        unsafe {
+            if start > reserved {
+                mmap::unmap(reserved as *const usize, start - reserved);
+            }
+            if reserved + reserve_size > end {
+                mmap::unmap(end as *const usize, reserved + reserve_size - end);
+            }
+        }
         Ok ((start, load_bias, end))
     }
 }
 
-/// Loads an ELF binary from the given fd, mmaps its contents, and returns a SharedObject, whose lifetime is tied to the mmap's, i.e., manually managed
-pub fn load<'a> (soname: &str, load_path: String, fd: &mut File, debug: bool, lachesis: &mut tls::Lachesis) -> Result <SharedObject<'a>, String> {
-
-    ///////////////
-    // Part I:
-    //   wherein we read the binary from disk,
-    //   and lovingly mmap it's joyous contents
-    ///////////////
+#[cfg(target_arch = "x86_64")]
+const EM_HOST: u16 = header::EM_X86_64;
+#[cfg(target_arch = "aarch64")]
+const EM_HOST: u16 = header::EM_AARCH64;
+#[cfg(target_arch = "arm")]
+const EM_HOST: u16 = header::EM_ARM;
+
+#[cfg(target_pointer_width = "64")]
+const ELFCLASS_HOST: u8 = header::ELFCLASS64;
+#[cfg(target_pointer_width = "32")]
+const ELFCLASS_HOST: u8 = header::ELFCLASS32;
+
+fn class_to_str (class: u8) -> String {
+    match class {
+        header::ELFCLASS32 => "ELFCLASS32".to_string(),
+        header::ELFCLASS64 => "ELFCLASS64".to_string(),
+        class => format!("unknown class {}", class),
+    }
+}
 
-    // 1. Suck up the elf header on disk and construct the program headers
-    let ehdr = header::Header::from_fd(fd).map_err(|e| format!("Error {:?}", e))?;
-    let phdrs = program_header::ProgramHeader::from_fd(fd, ehdr.e_phoff as u64, ehdr.e_phnum as usize).map_err(|e| format!("Error {:?}", e))?;
+/// Checks the elf header describes a shared object (or if `executable`, a program) we can actually load on this machine, and that the program header table fits in the file
+pub fn verify_header (ehdr: &header::Header, file_size: usize, executable: bool) -> Result<(), String> {
+    if &ehdr.e_ident[0..header::SELFMAG] != header::ELFMAG {
+        return Err ("Error: invalid ELF header".to_string())
+    }
+    if ehdr.e_ident[header::EI_CLASS] != ELFCLASS_HOST {
+        return Err (format!("Error: wrong ELF class: {}", class_to_str(ehdr.e_ident[header::EI_CLASS])))
+    }
+    if ehdr.e_ident[header::EI_DATA] != header::ELFDATA2LSB {
+        return Err ("Error: ELF file data encoding not little-endian".to_string())
+    }
+    if ehdr.e_ident[header::EI_VERSION] != header::EV_CURRENT {
+        return Err ("Error: ELF file version ident does not match current one".to_string())
+    }
+    if ehdr.e_machine != EM_HOST {
+        return Err (format!("Error: wrong machine type {} for this host", ehdr.e_machine))
+    }
+    match ehdr.e_type {
+        header::ET_DYN => (),
+        header::ET_EXEC if executable => (),
+        header::ET_EXEC => return Err ("Error: cannot load ET_EXEC as shared object".to_string()),
+        e_type => return Err (format!("Error: only ET_DYN can be loaded as a shared object, found e_type {}", e_type)),
+    }
+    if ehdr.e_phentsize as usize != mem::size_of::<program_header::ProgramHeader>() {
+        return Err ("Error: ELF file's phentsize not the expected size".to_string())
+    }
+    if ehdr.e_phnum == 0 {
+        return Err ("Error: ELF file has no program headers".to_string())
+    }
+    let phdrs_size = ehdr.e_phnum as usize * ehdr.e_phentsize as usize;
+    match (ehdr.e_phoff as usize).checked_add(phdrs_size) {
+        Some (end) if end <= file_size => Ok (()),
+        _ => Err ("Error: ELF program headers extend past the end of the file".to_string()),
+    }
+}
 
-    // 2. Reserve address space with anon mmap
-    let (start, load_bias, end) = reserve_address_space(&phdrs)?;
-    dbgc!(red_bold: debug, "loader", "reserved {:#x} - {:#x} with load_bias: 0x{:x}", start, end, load_bias);
-
-    // 3. Now we iterate through the phdrs, and
-    // a. mmap the PT_LOAD program headers
-    // b. collect the vaddrs of the phdrs and the dynamic array
-    // c. TODO: mmap and setup TLS
-    let mut phdrs_vaddr = 0usize;
-    let mut dynamic_vaddr = None;
+/// Checks the program headers are ones we can mmap: every loadable segment's file contents lie within the file, and its offset and vaddr are congruent modulo the page size
+pub fn verify_phdrs (phdrs: &[program_header::ProgramHeader], file_size: usize) -> Result<(), String> {
     let mut has_pt_load = false;
-    let mut tls = None;
-    for phdr in &phdrs {
-
+    for phdr in phdrs {
         match phdr.p_type {
+            program_header::PT_LOAD => {
+                has_pt_load = true;
+                match (phdr.p_offset as usize).checked_add(phdr.p_filesz as usize) {
+                    Some (end) if end <= file_size => (),
+                    _ => return Err (format!("Error: ELF load command at offset {:#x} with size {:#x} extends past the end of the file", phdr.p_offset, phdr.p_filesz)),
+                }
+                if phdr.p_filesz > phdr.p_memsz {
+                    return Err ("Error: ELF load command has p_filesz greater than p_memsz".to_string())
+                }
+                if (phdr.p_vaddr as usize).checked_add(phdr.p_memsz as usize).is_none() {
+                    return Err ("Error: ELF load command address wraps around".to_string())
+                }
+                if page::page_offset(phdr.p_vaddr as usize) != page::page_offset(phdr.p_offset as usize) {
+                    return Err ("Error: ELF load command address/offset not page-aligned".to_string())
+                }
+                if phdr.p_align > 1 && !(phdr.p_align as usize).is_power_of_two() {
+                    return Err ("Error: ELF load command alignment not a power of two".to_string())
+                }
+            },
+            program_header::PT_DYNAMIC => {
+                if (phdr.p_offset as usize).checked_add(phdr.p_filesz as usize).map(|end| end > file_size).unwrap_or(true) {
+                    return Err ("Error: ELF dynamic segment extends past the end of the file".to_string())
+                }
+            },
+            _ => (),
+        }
+    }
+    if !has_pt_load {
+        return Err ("Error: object file has no loadable segments".to_string())
+    }
+    Ok (())
+}
+
+/// Where an object's bytes come from: a file (including a memfd, or an object embedded in some larger file, e.g., an uncompressed apk), or a buffer already in memory
+pub enum Source<'a> {
+    /// The object starts at `offset` (which must be page aligned, since we mmap from it) in the file open at `fd`
+    Fd { fd: RawFd, offset: usize },
+    /// The whole object is in this buffer, e.g., it arrived over a socket; we copy it into fresh pages, since there's nothing to mmap
+    Memory (&'a [u8]),
+}
+
+impl<'a> Source<'a> {
+    pub fn from_file (fd: &File) -> Source<'static> {
+        Source::Fd { fd: fd.as_raw_fd(), offset: 0 }
+    }
 
-            program_header::PT_PHDR => {
-                phdrs_vaddr = phdr.p_vaddr as usize;
+    /// The identity of the file this is, if it is one
+    pub fn file_id (&self) -> Option<FileId> {
+        match *self {
+            Source::Fd { fd, offset } => {
+                let mut stat: libc::stat = unsafe { mem::zeroed() };
+                if unsafe { libc::fstat(fd, &mut stat) } < 0 {
+                    return None
+                }
+                Some (FileId { dev: stat.st_dev as u64, ino: stat.st_ino as u64, offset: offset })
             },
+            Source::Memory (_) => None,
+        }
+    }
 
-            program_header::PT_DYNAMIC => {
-                dynamic_vaddr = Some(phdr.p_vaddr as usize);
+    /// How many bytes of object we have, i.e., the file size less the offset
+    fn size (&self) -> Result<usize, String> {
+        match *self {
+            Source::Fd { fd, offset } => {
+                let mut stat: libc::stat = unsafe { mem::zeroed() };
+                if unsafe { libc::fstat(fd, &mut stat) } < 0 {
+                    return Err (format!("Error: cannot stat shared object file with errno {}", utils::get_errno()))
+                }
+                Ok ((stat.st_size as usize).saturating_sub(offset))
             },
+            Source::Memory (bytes) => Ok (bytes.len()),
+        }
+    }
 
-            program_header::PT_TLS => {
-                // remove tls info completely out of the SharedObject?
-                tls = Some (lachesis.push_module(soname, load_bias, &phdr));
+    /// Fills `buf` with the bytes at `offset` in the object; callers have already checked this is within `size`
+    fn read_at (&self, offset: usize, buf: &mut [u8]) -> Result<(), String> {
+        match *self {
+            Source::Fd { fd, offset: base } => {
+                let mut read = 0;
+                while read < buf.len() {
+                    let n = unsafe { libc::pread(fd, buf[read..].as_mut_ptr() as *mut libc::c_void, buf.len() - read, (base + offset + read) as libc::off_t) };
+                    if n <= 0 {
+                        return Err (format!("Error: cannot read file data with errno {}", utils::get_errno()))
+                    }
+                    read += n as usize;
+                }
+                Ok (())
             },
+            Source::Memory (bytes) => {
+                buf.copy_from_slice(&bytes[offset..offset + buf.len()]);
+                Ok (())
+            },
+        }
+    }
+
+    /// Maps `len` bytes of the object starting at (page aligned) `offset` over `addr` with protections `prot`
+    unsafe fn map (&self, addr: usize, len: usize, prot: isize, offset: usize) -> Result<(), String> {
+        match *self {
+            Source::Fd { fd, offset: base } => {
+                let mmap_flags = mmap::MAP_FIXED | mmap::MAP_PRIVATE;
+                if mmap::mmap(addr as *const usize, len, prot, mmap_flags as libc::c_int, fd as libc::c_int, base + offset) == mmap::MAP_FAILED {
+                    return Err (format!("Error: mmap failed with errno {}", utils::get_errno()))
+                }
+            },
+            Source::Memory (bytes) => {
+                // no file to map, so get writable anonymous pages, fill them in, and only then give them the segment's protections
+                let mmap_flags = mmap::MAP_FIXED | mmap::MAP_PRIVATE | mmap::MAP_ANONYMOUS;
+                if mmap::mmap(addr as *const usize, len, mmap::PROT_READ | mmap::PROT_WRITE, mmap_flags as libc::c_int, -1, 0) == mmap::MAP_FAILED {
+                    return Err (format!("Error: anonymous mmap failed with errno {}", utils::get_errno()))
+                }
+                ptr::copy_nonoverlapping(bytes[offset..].as_ptr(), addr as *mut u8, len);
+                if !mmap::protect(addr, addr + len, prot) {
+                    return Err (format!("Error: mprotect failed with errno {}", utils::get_errno()))
+                }
+            },
+        }
+        Ok (())
+    }
+}
+
+#[test]
+fn file_id_t () {
+    use std::env;
+    use std::fs;
+    use std::io::Write;
+    use std::os::unix::fs::symlink;
+    use std::path::Path;
+
+    let dir = env::temp_dir().join(format!("dryad-file-id-{}", unsafe { libc::getpid() }));
+    fs::create_dir_all(&dir).unwrap();
+    let lib = dir.join("libfoo.so.1");
+    File::create(&lib).unwrap().write_all(b"\x7fELF").unwrap();
+    let other = dir.join("libbar.so.1");
+    File::create(&other).unwrap().write_all(b"\x7fELF").unwrap();
+    let link = dir.join("libfoo.so");
+    let _ = fs::remove_file(&link);
+    symlink(&lib, &link).unwrap();
+    // the same file, but relative to the cwd, by way of /
+    let cwd = env::current_dir().unwrap();
+    let relative = Path::new(&"../".repeat(cwd.components().count() - 1)).join(lib.strip_prefix("/").unwrap());
+
+    let id = |path: &Path| Source::from_file(&File::open(path).unwrap()).file_id();
+    assert!(id(&lib).is_some());
+    assert_eq!(id(&lib), id(&link));
+    assert_eq!(id(&lib), id(&relative));
+    assert!(id(&lib) != id(&other));
+    // the same file at a different offset is a different object
+    let fd = File::open(&lib).unwrap();
+    assert!(Source::Fd { fd: fd.as_raw_fd(), offset: 0 }.file_id() != Source::Fd { fd: fd.as_raw_fd(), offset: 0x1000 }.file_id());
+    assert_eq!(Source::Memory (b"\x7fELF").file_id(), None);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// The elf header and program headers `verify` read and checked, which whoever verified a file hands on to `load`, rather than have it read them all over again
+pub type Verified = (header::Header, Vec<program_header::ProgramHeader>);
+
+/// Reads and validates the elf header and program headers from `source`, which must be a shared object unless `executable`; anything malformed or built for another machine is an error, never a crash
+pub fn verify (source: &Source, executable: bool) -> Result<Verified, String> {
+    let file_size = source.size()?;
+    if file_size < mem::size_of::<header::Header>() {
+        return Err ("Error: file too short".to_string())
+    }
+    let mut bytes = vec![0u8; mem::size_of::<header::Header>()];
+    source.read_at(0, &mut bytes)?;
+    let ehdr = unsafe { ptr::read_unaligned(bytes.as_ptr() as *const header::Header) };
+    verify_header(&ehdr, file_size, executable)?;
+
+    let phentsize = mem::size_of::<program_header::ProgramHeader>();
+    let mut bytes = vec![0u8; ehdr.e_phnum as usize * phentsize];
+    source.read_at(ehdr.e_phoff as usize, &mut bytes)?;
+    let phdrs = bytes.chunks(phentsize).map(|phdr| unsafe { ptr::read_unaligned(phdr.as_ptr() as *const program_header::ProgramHeader) }).collect::<Vec<_>>();
+    verify_phdrs(&phdrs, file_size)?;
+    Ok ((ehdr, phdrs))
+}
+
+/// Where the program headers will be once the segments are mapped: `PT_PHDR` says so if present, otherwise (like glibc) we find the `PT_LOAD` whose file contents include them.
+/// Either way they have to be inside the file-backed part of some segment, or we'd build our phdrs slice out of .bss or unmapped memory.
+fn mapped_phdrs_vaddr (ehdr: &header::Header, phdrs: &[program_header::ProgramHeader]) -> Result<usize, String> {
+    let size = phdrs.len() * mem::size_of::<program_header::ProgramHeader>();
+    let loaded = |vaddr: usize| {
+        phdrs.iter().any(|phdr| {
+            phdr.p_type == program_header::PT_LOAD
+                && vaddr >= phdr.p_vaddr as usize
+                && vaddr.checked_add(size).map(|end| end <= phdr.p_vaddr as usize + phdr.p_filesz as usize).unwrap_or(false)
+        })
+    };
+    let vaddr = match phdrs.iter().find(|phdr| phdr.p_type == program_header::PT_PHDR) {
+        Some (phdr) => Some (phdr.p_vaddr as usize),
+        None => phdrs.iter().find(|phdr| {
+            phdr.p_type == program_header::PT_LOAD
+                && ehdr.e_phoff >= phdr.p_offset
+                && ehdr.e_phoff - phdr.p_offset < phdr.p_filesz
+        }).map(|phdr| (phdr.p_vaddr + (ehdr.e_phoff - phdr.p_offset)) as usize),
+    };
+    match vaddr {
+        Some (vaddr) if loaded(vaddr) => Ok (vaddr),
+        _ => Err ("Error: ELF program headers are not in a loaded segment".to_string()),
+    }
+}
+
+/// Reserves address space for `phdrs` according to `placement`, and maps their `PT_LOAD` segments from `source` into it, .bss and all;
+/// returns the start of the mapping, the load bias, and the end of the mapping
+fn map_segments (soname: &str, source: &Source, phdrs: &[program_header::ProgramHeader], placement: Placement) -> Result<(usize, usize, usize), String> {
+    let mapping = trace::start();
+    // 2. Reserve address space with anon mmap
+    let (start, load_bias, end) = reserve_address_space(phdrs, placement)?;
+    dbgc!(red_bold: FILES, "loader", "reserved {:#x} - {:#x} with load_bias: 0x{:x}", start, end, load_bias);
+
+    // 3. Now we iterate through the phdrs, and mmap the PT_LOAD program headers
+    let mut has_pt_load = false;
+    for phdr in phdrs {
+
+        match phdr.p_type {
 
             program_header::PT_LOAD => {
                 has_pt_load = true;
@@ -119,10 +420,10 @@ pub fn load<'a> (soname: &str, load_path: String, fd: &mut File, debug: bool, la
                 let seg_end = seg_start + phdr.p_memsz as usize;
 
                 let seg_page_start = page::page_start(seg_start);
-                let seg_page_end = page::page_start(seg_end);
+                let seg_page_end = page::page_end(seg_end);
 
-                // TODO: unused, I think we need to zero some stuff
-                let seg_file_end = seg_start + phdr.p_filesz as usize;
+                // where the file contents stop; everything from here to seg_end is .bss
+                let mut seg_file_end = seg_start + phdr.p_filesz as usize;
 
                 // File offsets.
                 let file_start = phdr.p_offset as usize;
@@ -134,30 +435,43 @@ pub fn load<'a> (soname: &str, load_path: String, fd: &mut File, debug: bool, la
                 let file_page_start = page::page_start(file_start);
                 let file_length = file_end - file_page_start;
 
-                // TODO: add error checking, if file size <= 0, if file_end greater than file_size, etc.
+                dbgc!(red_bold: FILES, "loader", "PT_LOAD:\n\tseg_start: {:x} seg_end: {:x} seg_page_start: {:x} seg_page_end: {:x} seg_file_end: {:x}\n\tfile_start: {:x} file_end: {:x} file_page_start: {:x} file_length: {:x}", seg_start, seg_end, seg_page_start, seg_page_end, seg_file_end, file_start, file_end, file_page_start, file_length);
 
-                dbgc!(red_bold: debug, "loader", "PT_LOAD:\n\tseg_start: {:x} seg_end: {:x} seg_page_start: {:x} seg_page_end: {:x} seg_file_end: {:x}\n\tfile_start: {:x} file_end: {:x} file_page_start: {:x} file_length: {:x}", seg_start, seg_end, seg_page_start, seg_page_end, seg_file_end, file_start, file_end, file_page_start, file_length);
+                let prot_flags = mmap::pflags_to_prot(phdr.p_flags);
 
                 if file_length != 0 {
-                    let mmap_flags = mmap::MAP_FIXED | mmap::MAP_PRIVATE;
-                    let prot_flags = mmap::pflags_to_prot(phdr.p_flags);
                     unsafe {
-                        let start = mmap::mmap(seg_page_start as *const usize,
-                                               file_length as usize,
-                                               prot_flags,
-                                               mmap_flags as libc::c_int,
-                                               fd.as_raw_fd() as libc::c_int,
-                                               file_page_start as usize);
-
-                        if start == mmap::MAP_FAILED {
-
-                            return Err(format!("Error: loader loading phdrs for {} failed with errno {}, aborting execution", &soname, utils::get_errno()))
+                        if let Err (e) = source.map(seg_page_start, file_length, prot_flags, file_page_start) {
+                            release(start, end, placement);
+                            return Err (format!("Error: loader loading phdrs for {} failed ({}), aborting execution", &soname, e.trim_left_matches("Error: ")))
                         }
                     }
 
-                    // TODO: other more boring shit to do with zero'd out extra pages if too big, etc.
-                    //seg_file_end = page::page_end(seg_file_end);
+                    // the last file-backed page holds whatever comes after the segment in the file (usually the start of the section headers), but the .bss needs zeros;
+                    // bionic only bothers if the segment is writable, since otherwise no one could have put anything but zeros in the .bss anyway
+                    if phdr.p_flags & program_header::PF_W != 0 && page::page_offset(seg_file_end) > 0 {
+                        unsafe { ptr::write_bytes(seg_file_end as *mut u8, 0, page::PAGE_SIZE - page::page_offset(seg_file_end)); }
+                    }
+                    seg_file_end = page::page_end(seg_file_end);
+                } else {
+                    // nothing from the file at all, so the whole segment is .bss
+                    seg_file_end = seg_page_start;
+                }
 
+                // seg_file_end is now the first page after the file contents; any remaining .bss pages are fresh anonymous (hence zero) memory with the segment's own protections
+                if seg_page_end > seg_file_end {
+                    let mmap_flags = mmap::MAP_FIXED | mmap::MAP_PRIVATE | mmap::MAP_ANONYMOUS;
+                    let bss = unsafe { mmap::mmap(seg_file_end as *const usize,
+                                                    seg_page_end - seg_file_end,
+                                                    prot_flags,
+                                                    mmap_flags as libc::c_int,
+                                                    -1,
+                                                    0) };
+                    if bss == mmap::MAP_FAILED {
+                        let errno = utils::get_errno();
+                        release(start, end, placement);
+                        return Err(format!("Error: loader mapping .bss for {} failed with errno {}, aborting execution", &soname, errno))
+                    }
                 }
             }, // end match PT_LOAD
             _ => () // do nothing, i.e., continue
@@ -165,74 +479,201 @@ pub fn load<'a> (soname: &str, load_path: String, fd: &mut File, debug: bool, la
     }
 
     if !has_pt_load {
+        release(start, end, placement);
         return Err(format!("Error: loader {} has no PT_LOAD sections", soname));
     }
+    trace::span("mmap", soname, mapping);
 
-    ///////////////
-    // Part Deux:
-    //   wherein we construct our components for this SharedObject
-    //   from the newly mmap'd memory
-    ///////////////
+    Ok ((start, load_bias, end))
+}
 
-    // use the now mmap'd program headers
-    let phdrs = unsafe { program_header::ProgramHeader::from_raw_parts((phdrs_vaddr + load_bias) as *const program_header::ProgramHeader, phdrs.len()) };
+/// Gives back `[start, end)`, which `reserve_address_space` reserved according to `placement` and we may have mapped segments over since, e.g., when a load fails part way;
+/// a caller's reservation goes back to being reserved (`PROT_NONE`), rather than being unmapped out from under them
+fn release (start: usize, end: usize, placement: Placement) {
+    if start >= end {
+        return
+    }
+    unsafe {
+        match placement {
+            Placement::Reserved { addr, size, .. } if start >= addr && end <= addr + size => {
+                let mmap_flags = mmap::MAP_FIXED | mmap::MAP_PRIVATE | mmap::MAP_ANONYMOUS;
+                mmap::mmap(start as *const usize, end - start, mmap::PROT_NONE, mmap_flags as libc::c_int, -1, 0);
+            },
+            _ => {
+                mmap::unmap(start as *const usize, end - start);
+            },
+        }
+    }
+}
 
-    // construct the dynamic slice in whatever mmap'd PT_LOAD it's in
-    let dynamic_vaddr = dynamic_vaddr.ok_or(format!("Error: loader {} has no dynamic array", soname))?;
-    let dynamic = unsafe { dyn::from_raw(load_bias, dynamic_vaddr) };
+/// Unmaps everything `load` mapped for `so` with `placement`, for when a dlopen fails after loading it; `so` mustn't be touched afterwards
+pub fn unload (so: &SharedObject, placement: Placement) {
+    release(so.map_begin, so.map_end, placement);
+}
 
-    // build the link info with the bias and the dynamic array
-    let link_info = dyn::DynamicInfo::new(&dynamic, load_bias as usize);
+/// Loads an ELF binary from the given source, mmaps its contents, and returns a SharedObject, whose lifetime is tied to the mmap's, i.e., manually managed.
+/// `verified` is what `verify` said about `source`, which the caller has already had to check anyway, to know it's worth loading
+pub fn load<'a> (soname: &str, load_path: String, source: &Source, verified: Verified, placement: Placement, lachesis: &mut tls::Lachesis) -> Result <SharedObject<'a>, String> {
 
-    // now get the strtab from the dynamic array
-    let strtab = unsafe { Strtab::from_raw(link_info.strtab as *const u8, link_info.strsz as usize, 0x0) };
+    ///////////////
+    // Part I:
+    //   wherein we read the binary from disk,
+    //   and lovingly mmap it's joyous contents
+    ///////////////
 
-    // now get the libs we will need
-    let libs = unsafe { dyn::get_needed(dynamic, &strtab, link_info.needed_count) };
+    // 1. The elf header and program headers, which were sucked up from disk and checked for sanity before anyone called us
+    let (ehdr, phdrs) = verified;
 
-    // caveat about rdr doing this for hundres of binaries and it being "ok"
-    let num_syms = (link_info.strtab - link_info.symtab) / sym::SIZEOF_SYM;
+    let phdrs_vaddr = mapped_phdrs_vaddr(&ehdr, &phdrs)?;
 
-    // now construct the symtab
-    let symtab = unsafe { sym::from_raw(link_info.symtab as *const sym::Sym, num_syms) };
+    // 2. Reserve address space with anon mmap, and 3. mmap the PT_LOAD program headers into it
+    let (start, load_bias, end) = map_segments(soname, source, &phdrs, placement)?;
 
-    // now grab relocations, and pltreltab which we'll use for relocating this shared object later
-    #[cfg(target_pointer_width = "32")]
-    let relocations = unsafe { reloc::from_raw_rel(link_info.rel as *const reloc::Rel, link_info.relsz) };
-    #[cfg(target_pointer_width = "32")]
-    let pltrelocations = unsafe { reloc::from_raw_rel(link_info.jmprel as *const reloc::Rel, link_info.pltrelsz) };
-    #[cfg(target_pointer_width = "64")]
-    let relocations = unsafe { reloc::from_raw_rela(link_info.rela as *const reloc::Rela, link_info.relasz) };
-    #[cfg(target_pointer_width = "64")]
-    let pltrelocations = unsafe { reloc::from_raw_rela(link_info.jmprel as *const reloc::Rela, link_info.pltrelsz) };
+    ///////////////
+    // Part Deux:
+    //   wherein we construct our components for this SharedObject
+    //   from the newly mmap'd memory
+    ///////////////
 
-    // the pltgot we need for doing lazy dynamic linking
-    let pltgot = if let Some(addr) = link_info.pltgot { addr } else { 0 }; // musl doesn't have a PLTGOT, for example
+    // use the now mmap'd program headers
+    let mapped = unsafe { program_header::ProgramHeader::from_raw_parts((phdrs_vaddr + load_bias) as *const program_header::ProgramHeader, phdrs.len()) };
+
+    // construct the dynamic slice, link info, symtab, strtab, relocations, etc., checking all of it lies in the segments we just mapped
+    let parsing = trace::start();
+    let shared_object = match unsafe { SharedObject::from_mapped(&load_path, load_bias, mapped) } {
+        Ok (so) => so,
+        Err (e) => {
+            release(start, end, placement);
+            return Err (e)
+        }
+    };
+    trace::span("parse", soname, parsing);
 
-    // and finally grab the gnu_hash (if it has one)
-    let gnu_hash = if let Some(addr) = link_info.gnu_hash {
-        Some (unsafe { GnuHash::new(addr as *const u32, symtab.len(), symtab) })
-    } else { None };
+    // 4. TODO: mmap and setup TLS
+    // only now it's parsed, so a broken object doesn't use up a module id
+    let mut tls = None;
+    let mut relro = None;
+    for phdr in &phdrs {
+        match phdr.p_type {
+            program_header::PT_TLS => {
+                // remove tls info completely out of the SharedObject?
+                tls = Some (lachesis.push_module(soname, load_bias, &phdr));
+            },
+            program_header::PT_GNU_RELRO => {
+                relro = Some (image::relro_range(load_bias, &phdr));
+            },
+            _ => ()
+        }
+    }
 
     let shared_object = SharedObject {
-        load_bias: load_bias,
-        libs: libs,
         map_begin: start,
         map_end: end,
-        phdrs: phdrs,
-        dynamic: dynamic,
-        symtab: symtab,
-        strtab: strtab,
-        relocations: relocations,
-        pltrelocations: pltrelocations,
-        pltgot: pltgot as *const usize,
-        gnu_hash: gnu_hash,
         load_path: Some (load_path),
-        flags: link_info.flags as usize,
-        state_flags: link_info.flags_1 as usize,
+        file_id: source.file_id(),
+        relro: relro,
         tls: tls,
-        link_info: link_info,
+        .. shared_object
     };
 
     Ok (shared_object)
 }
+
+/// Maps the program in `source` the way the kernel would have had it exec'd us as its interpreter, for when we're run as `dryad.so.1 ./program args...`;
+/// returns where its program headers ended up, how many there are, and its (biased) entry point, i.e., what the kernel would have put in `AT_PHDR`, `AT_PHNUM` and `AT_ENTRY`.
+/// There's no `SharedObject` for it here (nor tls module); `Linker::link` builds one from the auxv, exactly as if the kernel had mapped it.
+pub fn load_executable (name: &str, source: &Source) -> Result<(usize, usize, usize), String> {
+    let (ehdr, phdrs) = verify(source, true)?;
+    if !phdrs.iter().any(|phdr| phdr.p_type == program_header::PT_DYNAMIC) {
+        return Err (format!("Error: {} is not a dynamic executable", name))
+    }
+    let phdrs_vaddr = mapped_phdrs_vaddr(&ehdr, &phdrs)?;
+    let placement = if ehdr.e_type == header::ET_EXEC { Placement::Fixed } else { Placement::Anywhere };
+    let (_, load_bias, _) = map_segments(name, source, &phdrs, placement)?;
+    dbgc!(red_bold: FILES, "loader", "mapped program {} with load_bias: 0x{:x}", name, load_bias);
+    Ok ((phdrs_vaddr + load_bias, phdrs.len(), (ehdr.e_entry as usize).wrapping_add(load_bias)))
+}
+
+/// The (page aligned) current offset of `fd`, which is where the RELRO functions below read or write
+fn relro_offset (fd: RawFd) -> Result<usize, String> {
+    let offset = unsafe { libc::lseek(fd, 0, libc::SEEK_CUR) };
+    if offset < 0 {
+        Err (format!("Error: cannot seek relro file with errno {}", utils::get_errno()))
+    } else if page::page_offset(offset as usize) != 0 {
+        Err (format!("Error: relro file offset {:#x} is not page aligned", offset))
+    } else {
+        Ok (offset as usize)
+    }
+}
+
+/// Writes the (relocated) `PT_GNU_RELRO` of `so` to `fd` at its current offset, then maps that back over the original,
+/// so this process already shares the pages with any other which later uses the file with `map_relro`.
+/// Like bionic's `phdr_table_serialize_gnu_relro`; the offset is left after what we wrote, so several objects can share a file.
+pub fn serialize_relro (so: &SharedObject, fd: RawFd) -> Result<(), String> {
+    let (start, end) = match so.relro { Some ((start, end)) if start < end => (start, end), _ => return Ok (()) };
+    let offset = relro_offset(fd)?;
+    let size = end - start;
+    let mut written = 0;
+    while written < size {
+        let n = unsafe { libc::write(fd, (start + written) as *const libc::c_void, size - written) };
+        if n <= 0 {
+            return Err (format!("Error: cannot write relro of {} with errno {}", so.path(), utils::get_errno()))
+        }
+        written += n as usize;
+    }
+    let mmap_flags = mmap::MAP_FIXED | mmap::MAP_PRIVATE;
+    if unsafe { mmap::mmap(start as *const usize, size, mmap::PROT_READ, mmap_flags as libc::c_int, fd, offset) } == mmap::MAP_FAILED {
+        return Err (format!("Error: cannot map relro of {} back from file with errno {}", so.path(), utils::get_errno()))
+    }
+    Ok (())
+}
+
+/// Maps the pages of a RELRO previously written by `serialize_relro` (at `fd`'s current offset) over `so`'s, wherever they're identical to what we just relocated;
+/// this way every process using the file shares the pages, and if the file is stale (different library, different load address) we just don't share the pages which differ.
+/// Like bionic's `phdr_table_map_gnu_relro`.
+pub fn map_relro (so: &SharedObject, fd: RawFd) -> Result<(), String> {
+    let (start, end) = match so.relro { Some ((start, end)) if start < end => (start, end), _ => return Ok (()) };
+    let offset = relro_offset(fd)?;
+    let size = end - start;
+
+    // reading past the end of a mapped file is a SIGBUS, so make sure it's all there first
+    let mut stat: libc::stat = unsafe { mem::zeroed() };
+    if unsafe { libc::fstat(fd, &mut stat) } < 0 || (stat.st_size as usize) < offset + size {
+        return Err (format!("Error: relro file is too short for {}", so.path()))
+    }
+
+    unsafe {
+        let file = mmap::mmap(0 as *const usize, size, mmap::PROT_READ, mmap::MAP_PRIVATE as libc::c_int, fd, offset);
+        if file == mmap::MAP_FAILED {
+            return Err (format!("Error: cannot map relro file with errno {}", utils::get_errno()))
+        }
+        let ours = slice::from_raw_parts(start as *const u8, size);
+        let theirs = slice::from_raw_parts(file as *const u8, size);
+
+        // map each run of identical pages in one go
+        let mmap_flags = mmap::MAP_FIXED | mmap::MAP_PRIVATE;
+        let npages = size / page::PAGE_SIZE;
+        let same = |i: usize| ours[i * page::PAGE_SIZE..(i + 1) * page::PAGE_SIZE] == theirs[i * page::PAGE_SIZE..(i + 1) * page::PAGE_SIZE];
+        let mut i = 0;
+        let mut result = Ok (());
+        while i < npages {
+            if !same(i) {
+                i += 1;
+                continue
+            }
+            let first = i;
+            while i < npages && same(i) {
+                i += 1;
+            }
+            let addr = start + first * page::PAGE_SIZE;
+            let len = (i - first) * page::PAGE_SIZE;
+            if mmap::mmap(addr as *const usize, len, mmap::PROT_READ, mmap_flags as libc::c_int, fd, offset + first * page::PAGE_SIZE) == mmap::MAP_FAILED {
+                result = Err (format!("Error: cannot map relro of {} from file with errno {}", so.path(), utils::get_errno()));
+                break
+            }
+        }
+        mmap::unmap(file as *const usize, size);
+        libc::lseek(fd, (offset + size) as libc::off_t, libc::SEEK_SET);
+        result
+    }
+}
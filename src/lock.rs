@@ -0,0 +1,86 @@
+/// The global linker lock, i.e., glibc's `dl_load_lock`.
+///
+/// Anything which reads or mutates the link map once the program is running must hold this: the lazy binding slow path in `dryad_resolve_symbol`,
+/// dlopen, running constructors (at startup too), and `dryad_dl_iterate_phdr`.  It is recursive, because constructors run with it held can themselves call dlopen, or take a lazy binding
+/// which re-enters the resolver on the same thread.
+///
+/// We can't use `std::sync::Mutex`: it isn't recursive, and it's poisoned across fork.
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+
+pub struct RecursiveLock {
+    /// The tid of the owning thread, or 0
+    owner: AtomicUsize,
+    /// How many times the owner has taken the lock; only ever touched by the owner
+    count: AtomicUsize,
+}
+
+pub struct Guard<'a> {
+    lock: &'a RecursiveLock,
+}
+
+impl<'a> Drop for Guard<'a> {
+    fn drop (&mut self) {
+        self.lock.unlock();
+    }
+}
+
+// the syscall crate knows the numbers for every arch we build for (x86_64, arm and aarch64), and it's the owner's tid the lock's recursion hangs off,
+// so there's no faking this on an arch it doesn't know
+#[inline(always)]
+pub fn gettid () -> usize {
+    unsafe { syscall!(GETTID) }
+}
+
+#[inline(always)]
+fn yield_now () {
+    unsafe { syscall!(SCHED_YIELD); }
+}
+
+impl RecursiveLock {
+    pub fn lock (&self) -> Guard {
+        let tid = gettid();
+        if self.owner.load(Ordering::Relaxed) == tid {
+            self.count.fetch_add(1, Ordering::Relaxed);
+        } else {
+            // contention is rare (dlopen racing a first call through the PLT), so just yield rather than bother with a futex
+            while self.owner.compare_and_swap(0, tid, Ordering::Acquire) != 0 {
+                yield_now();
+            }
+            self.count.store(1, Ordering::Relaxed);
+        }
+        Guard { lock: self }
+    }
+
+    fn unlock (&self) {
+        if self.count.fetch_sub(1, Ordering::Relaxed) == 1 {
+            self.owner.store(0, Ordering::Release);
+        }
+    }
+
+    /// Forcibly releases the lock; only valid in a freshly forked child, where the owning thread (if any) no longer exists
+    unsafe fn reset (&self) {
+        self.count.store(0, Ordering::Relaxed);
+        self.owner.store(0, Ordering::Release);
+    }
+}
+
+pub static LINK_LOCK: RecursiveLock = RecursiveLock { owner: ATOMIC_USIZE_INIT, count: ATOMIC_USIZE_INIT };
+
+// fork handlers, registered with libc's `__register_atfork` (or musl's `pthread_atfork`) once libc is up, see `Linker::register_atfork`;
+// prepare takes the lock so no other thread can be halfway through mutating the link map at the moment of the fork,
+// and both sides then release it, otherwise a child forked while some other thread held the lock would deadlock on its first lazy binding
+
+#[no_mangle]
+pub extern fn _dryad_atfork_prepare () {
+    ::std::mem::forget(LINK_LOCK.lock());
+}
+
+#[no_mangle]
+pub extern fn _dryad_atfork_parent () {
+    LINK_LOCK.unlock();
+}
+
+#[no_mangle]
+pub extern fn _dryad_atfork_child () {
+    unsafe { LINK_LOCK.reset(); }
+}
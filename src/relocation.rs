@@ -117,6 +117,6 @@ pub fn relocate_linker(bias: usize, info: &dyn::DynamicInfo, phdrs: &[program_he
         }
     }
     if info.textrel {
-        let res = utils::mmap::mprotect_phdrs(phdrs, bias, 0);
+        let res = utils::mmap::restore_phdrs(phdrs, bias);
     }
 }
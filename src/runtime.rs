@@ -1,43 +1,133 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
 use image::SharedObject;
+use scope::Scopes;
 use elf::reloc;
-use elf::gnu_hash;
+use elf::sym;
+use lock;
+use stats;
+use trace;
 use utils;
 
+// x86_64 has its own trampolines below, picked at startup; only everyone else still goes through this one
+#[cfg(not(target_arch = "x86_64"))]
 extern {
     /// The assembly stub which grabs the stack pointer, aligns and unwinds the stack into parameters and then calls `dryad_resolve_symbol` with those parameters.
     /// _Many_ thanks to Mutabah from `#rust@Mozilla` for suggesting the stack needed to be 16-byte aligned, after I experienced crashes on `movaps %xmm2,0x60(%rsp)`.
     pub fn _dryad_resolve_symbol();
 }
 
+#[cfg(target_arch = "x86_64")]
+extern {
+    /// See `arch/x86_64/trampoline.s`
+    fn _dryad_resolve_symbol_fxsave();
+    fn _dryad_resolve_symbol_xsave();
+    fn _dryad_resolve_symbol_xsavec();
+}
+
+/// The stack the xsave trampolines reserve: the 0x40 byte register save area plus an xsave area big enough for every state component the OS enabled in XCR0, rounded to 64 bytes.
+/// Written once by `select_trampoline`, read by the trampolines.
+#[no_mangle]
+pub static mut _dryad_xsave_state_size: usize = 0;
+
+#[cfg(target_arch = "x86_64")]
+#[inline(always)]
+unsafe fn cpuid (leaf: u32, subleaf: u32) -> (u32, u32, u32, u32) {
+    let eax: u32;
+    let ebx: u32;
+    let ecx: u32;
+    let edx: u32;
+    asm!("cpuid"
+         : "={eax}"(eax), "={ebx}"(ebx), "={ecx}"(ecx), "={edx}"(edx)
+         : "{eax}"(leaf), "{ecx}"(subleaf)
+         :
+         : "volatile");
+    (eax, ebx, ecx, edx)
+}
+
+/// Picks the lazy binding trampoline for this cpu, which `prepare_got` then puts in GOT[2]:
+/// xsavec if we have it, then xsave, and fxsave on anything so old it has neither (or whose kernel didn't enable them, i.e., OSXSAVE is clear)
+#[cfg(target_arch = "x86_64")]
+pub unsafe fn select_trampoline () -> usize {
+    const XSAVE: u32 = 1 << 26;
+    const OSXSAVE: u32 = 1 << 27;
+    const XSAVEC: u32 = 1 << 1;
+    let (max_leaf, _, _, _) = cpuid(0, 0);
+    let (_, _, features, _) = cpuid(1, 0);
+    if max_leaf >= 0xd && features & (XSAVE | OSXSAVE) == (XSAVE | OSXSAVE) {
+        // ebx of leaf 0xd, subleaf 0 is the size of the (uncompacted) xsave area for what's currently enabled in XCR0, which is also an upper bound on the compacted size
+        let (_, size, _, _) = cpuid(0xd, 0);
+        _dryad_xsave_state_size = (0x40 + size as usize + 63) & !63;
+        let (xsave_features, _, _, _) = cpuid(0xd, 1);
+        if xsave_features & XSAVEC != 0 {
+            dbgc!(blue_bold: BINDINGS, "dryad.runtime", "using xsavec trampoline with state size {:#x}", _dryad_xsave_state_size);
+            _dryad_resolve_symbol_xsavec as usize
+        } else {
+            dbgc!(blue_bold: BINDINGS, "dryad.runtime", "using xsave trampoline with state size {:#x}", _dryad_xsave_state_size);
+            _dryad_resolve_symbol_xsave as usize
+        }
+    } else {
+        dbgc!(blue_bold: BINDINGS, "dryad.runtime", "using fxsave trampoline");
+        _dryad_resolve_symbol_fxsave as usize
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+pub unsafe fn select_trampoline () -> usize {
+    _dryad_resolve_symbol as usize
+}
+
 /// The data structure which allows runtime lazy binding.  A pointer to this structure is placed in a binaries GOT[1] in `prepare_got`,
 /// and reconstructed in `dryad_resolve_symbol`
 #[repr(C)]
 pub struct Rendezvous<'a> {
     pub idx: usize,
-    pub debug: bool,
-    pub link_map: &'a[SharedObject<'a>],
+    /// `LD_BIND_NOT`: resolve, but never write the result back into the GOT, so every call comes back through here
+    pub bind_not: bool,
+    /// The linker's (boxed) link map itself rather than a slice of it, since `dlopen` can push onto it and reallocate at any time; only read it with the link lock held
+    pub link_map: *const Vec<SharedObject<'a>>,
+    /// Likewise the linker's (boxed) scopes, which `dlopen` adds to
+    pub scopes: *const Scopes,
 }
 
 #[no_mangle]
 pub extern fn dryad_resolve_symbol (rndzv_ptr: *const Rendezvous, rela_idx: usize) -> usize {
     unsafe {
+        // we only get here on the first call through a PLT slot (or every call with LD_BIND_NOT), so taking the lock is cheap enough,
+        // and it stops a dlopen on another thread from changing the link map out from under us
+        let _guard = lock::LINK_LOCK.lock();
+        let start = trace::start();
         let rndzv = &*rndzv_ptr; // dereference the data structure
-//        dbgc!(blue_bold: rndzv.debug, "dryad.runtime", "link_map_ptr: {:#?} rela_idx: {}", rndzv_ptr, rela_idx);
-        let link_map = rndzv.link_map;
+//        dbgc!(blue_bold: BINDINGS, "dryad.runtime", "link_map_ptr: {:#?} rela_idx: {}", rndzv_ptr, rela_idx);
+        let link_map: &[SharedObject] = &*rndzv.link_map;
         let requesting_so = &link_map[rndzv.idx]; // get who called us using the index in the data structure
         let rela = &requesting_so.pltrelocations[rela_idx]; // now get the relocation using the rela_idx the binary pushed onto the stack
-        let requested_symbol = &requesting_so.symtab[reloc::r_sym(rela.r_info) as usize]; // obtain the actual symbol being requested
+        let sym_idx = reloc::r_sym(rela.r_info) as usize;
+        let requested_symbol = &requesting_so.symtab[sym_idx]; // obtain the actual symbol being requested
         let name = &requesting_so.strtab[requested_symbol.st_name as usize]; // ... and now it's name, which we'll use to search
-//        dbgc!(blue_bold: rndzv.debug, "dryad.runtime", "reconstructed link_map of size {} with requesting binary {:#?} for symbol {} with rela idx {}", link_map.len(), requesting_so.name, name, rela_idx);
-        let hash = gnu_hash::hash(name);
-        for (i, so) in link_map.iter().enumerate() {
-            if let Some (sym) = so.find(name, hash) {
-//                dbgc!(blue_bold: rndzv.debug, "dryad.runtime", "binding \"{}\" in {} to {} at address 0x{:x}", name, so.name, requesting_so.name, symbol.st_value + so.load_bias);
-                return (sym.st_value as usize + so.load_bias as usize) as usize
+        let version = requesting_so.requested_version(sym_idx); // ... and the version it was linked against, if any
+//        dbgc!(blue_bold: BINDINGS, "dryad.runtime", "reconstructed link_map of size {} with requesting binary {:#?} for symbol {} with rela idx {}", link_map.len(), requesting_so.name, name, rela_idx);
+        stats::LAZY_LOOKUPS.fetch_add(1, Ordering::Relaxed);
+        let addr = match (*rndzv.scopes).lookup(link_map, rndzv.idx, name, version.as_ref()) {
+            Some ((sym, so)) => {
+//                dbgc!(blue_bold: BINDINGS, "dryad.runtime", "binding \"{}\" in {} to {} at address 0x{:x}", name, so.name, requesting_so.name, symbol.st_value + so.load_bias);
+                sym.st_value as usize + so.load_bias
+            },
+            // an unresolved weak reference is allowed, and binds to zero
+            None if sym::st_bind(requested_symbol.st_info) == sym::STB_WEAK => 0,
+            None => {
+                // same message and exit code as ld.so, so scripts grepping for it keep working
+                utils::write_err(&format!("dryad: symbol lookup error: {}: undefined symbol: {}\n", requesting_so.path(), name));
+                utils::_exit(127);
+                return 0xd47ad
             }
+        };
+        trace::span("bind", name, start);
+        if !rndzv.bind_not {
+            // patch the GOT slot the PLT entry jumps through, so the next call goes straight to `addr`;
+            // the store is atomic because another thread may be jumping through this slot right now, and if two threads race here they both store the same value
+            let got = (rela.r_offset as usize + requesting_so.load_bias) as *const AtomicUsize;
+            (*got).store(addr, Ordering::Release);
         }
-//        dbgc!(blue_bold: true, "dryad.runtime", "Error: symbol \"{}\" not found, aborting execution of {}, goodbye!", name, requesting_so.name);
-        utils::_exit(1);
-        0xd47ad
+        addr
     }
 }
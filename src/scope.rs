@@ -0,0 +1,251 @@
+/// Symbol search scopes, i.e., which objects a lookup walks, and in what order.
+///
+/// Like ld.so, there's one global scope: the executable, then `LD_PRELOAD`s, then everyone's `DT_NEEDED` breadth first, level by level, each object once.
+/// For the link map in `Linker::link`'s comment,
+/// ```
+/// exe
+/// |_ libfoo
+/// |_ libbar
+///    |_ libderp
+///    |_ libbaz
+///    |_ libfoo
+///    |_ libslerp
+///    |_ libmerp
+/// |_ libbaz
+///    |_ libmerp
+///    |_ libslerp
+/// ```
+/// that's `[exe, libfoo, libbar, libbaz, libderp, libslerp, libmerp]`.
+///
+/// Every object also has a local scope, its own dependency closure (again breadth first, starting with itself), which is searched after the global scope;
+/// this is what lets an object dlopen'd with `RTLD_LOCAL` find its dependencies, since none of them are in the global scope.
+///
+/// The same graph also decides what order constructors run in, see `init_order`.
+///
+/// Objects are indices into the link map, and the dependency graph is, for each object, the indices of its `DT_NEEDED`s in order, so all of this is pure.
+use std::collections::VecDeque;
+use elf::sym::Sym;
+use elf::gnu_hash;
+use image::SharedObject;
+use version::Version;
+
+/// Everything reachable from `roots`, breadth first, each object once
+fn breadth_first (roots: &[usize], deps: &[Vec<usize>]) -> Vec<usize> {
+    let mut seen = vec![false; deps.len()];
+    let mut queue = VecDeque::new();
+    for &root in roots {
+        if !seen[root] {
+            seen[root] = true;
+            queue.push_back(root);
+        }
+    }
+    let mut order = Vec::with_capacity(deps.len());
+    while let Some(idx) = queue.pop_front() {
+        order.push(idx);
+        for &dep in &deps[idx] {
+            if !seen[dep] {
+                seen[dep] = true;
+                queue.push_back(dep);
+            }
+        }
+    }
+    order
+}
+
+/// The global scope: `exe`, then `preloads`, then breadth first through all their dependencies
+pub fn global_scope (exe: usize, preloads: &[usize], deps: &[Vec<usize>]) -> Vec<usize> {
+    let mut roots = vec![exe];
+    roots.extend_from_slice(preloads);
+    breadth_first(&roots, deps)
+}
+
+/// The local scope of `root`: itself, then breadth first through its dependencies
+pub fn local_scope (root: usize, deps: &[Vec<usize>]) -> Vec<usize> {
+    breadth_first(&[root], deps)
+}
+
+/// The order to run constructors in: every object after all of its dependencies, i.e., a depth first post-order walk from each of `roots` in turn, following `DT_NEEDED`s in order.
+/// Like glibc, a cycle is broken wherever the walk closes it: of the objects in the cycle, the one the walk reached last is constructed first.
+pub fn init_order (roots: &[usize], deps: &[Vec<usize>]) -> Vec<usize> {
+    fn visit (idx: usize, deps: &[Vec<usize>], seen: &mut [bool], order: &mut Vec<usize>) {
+        seen[idx] = true;
+        for &dep in &deps[idx] {
+            if !seen[dep] {
+                visit(dep, deps, seen, order);
+            }
+        }
+        order.push(idx);
+    }
+    let mut seen = vec![false; deps.len()];
+    let mut order = Vec::with_capacity(deps.len());
+    for &root in roots {
+        if !seen[root] {
+            visit(root, deps, &mut seen, &mut order);
+        }
+    }
+    order
+}
+
+/// A symbol one of an object's relocations bound to
+#[derive(Debug)]
+pub struct Binding {
+    pub symbol: String,
+    pub version: Option<String>,
+    /// Link map index
+    pub provider: usize,
+    pub addr: usize,
+}
+
+/// What an object's relocations bound to.
+/// Only the symbol lookups in `relocate_got` and `relocate_plt` end up here; the tls ones (`DTPMOD64`, `DTPOFF64`, `TPOFF64`) and `COPY`s don't,
+/// so a library whose only use is a `__thread` variable or a copied data symbol looks unused to `ldd -u`. Good enough for now.
+#[derive(Debug, Default)]
+pub struct Bound {
+    /// Link map indices of everyone who satisfied at least one of them
+    pub providers: Vec<usize>,
+    /// Each binding, but only when someone asked to see them, since there can be tens of thousands
+    pub bindings: Vec<Binding>,
+}
+
+#[derive(Debug, Default)]
+pub struct Scopes {
+    pub global: Vec<usize>,
+    /// Indexed by link map index
+    pub local: Vec<Vec<usize>>,
+}
+
+impl Scopes {
+    /// Searches for `name` on behalf of the object at `idx`: in itself first if it's `DF_SYMBOLIC`, then the global scope, then whatever of its local scope isn't in the global scope.
+    /// Like ld.so (without `LD_DYNAMIC_WEAK`), a weak definition is as good as a global one; it's up to the caller to decide what an unresolved weak _reference_ binds to.
+    pub fn lookup<'a, 'process> (&self, link_map: &'a [SharedObject<'process>], idx: usize, name: &str, version: Option<&Version>) -> Option<(&'a Sym, &'a SharedObject<'process>)> {
+        self.lookup_index(link_map, idx, name, version).map(|(sym, i)| (sym, &link_map[i]))
+    }
+
+    /// `lookup`, but with the provider's link map index rather than the provider, for whoever's keeping track of who binds to whom
+    pub fn lookup_index<'a, 'process> (&self, link_map: &'a [SharedObject<'process>], idx: usize, name: &str, version: Option<&Version>) -> Option<(&'a Sym, usize)> {
+        // pre-hashed once for every object with a gnu hash table; the odd one with only a sysv one hashes the name itself
+        let hash = gnu_hash::hash(name);
+        let requester = &link_map[idx];
+        let symbolic = if requester.symbolic { Some (idx) } else { None };
+        let local: &[usize] = self.local.get(idx).map(|local| &local[..]).unwrap_or(&[]);
+        let global = &self.global;
+        let order = symbolic.into_iter()
+            .chain(global.iter().cloned())
+            .chain(local.iter().cloned().filter(|i| !global.contains(i)));
+        for i in order {
+            let provider = &link_map[i];
+            dbg!(SYMBOLS, "symbol={};  lookup in file={} [{}]", name, provider.name(), i);
+            if let Some(sym) = provider.find_versioned(name, hash, version) {
+                if let Some(version) = version {
+                    dbg!(VERSIONS, "checking for version `{}' in file {} [{}] required by file {} [{}]", version.name, provider.name(), i, requester.name(), idx);
+                    dbg!(BINDINGS, "binding file {} [{}] to {} [{}]: normal symbol `{}' [{}]", requester.name(), idx, provider.name(), i, name, version.name);
+                } else {
+                    dbg!(BINDINGS, "binding file {} [{}] to {} [{}]: normal symbol `{}'", requester.name(), idx, provider.name(), i, name);
+                }
+                return Some ((sym, i))
+            }
+        }
+        None
+    }
+}
+
+/// For each object, the `DT_NEEDED`s which satisfied none of its relocations, i.e., `ldd -u`,
+/// given the dependency graph and, for each object, what its relocations bound to.
+/// Returned as `(requester, dependency)` pairs, in link map order and then `DT_NEEDED` order.
+pub fn unused (deps: &[Vec<usize>], bound: &[Bound]) -> Vec<(usize, usize)> {
+    let mut unused = Vec::new();
+    for (idx, needed) in deps.iter().enumerate() {
+        let used = bound.get(idx).map(|bound| &bound.providers[..]).unwrap_or(&[]);
+        for &dep in needed {
+            if dep != idx && !used.contains(&dep) {
+                unused.push((idx, dep));
+            }
+        }
+    }
+    unused
+}
+
+#[test]
+fn global_scope_t () {
+    // the example above
+    let (exe, foo, bar, baz, derp, slerp, merp) = (0, 1, 2, 3, 4, 5, 6);
+    let mut deps = vec![vec![]; 7];
+    deps[exe] = vec![foo, bar, baz];
+    deps[bar] = vec![derp, baz, foo, slerp, merp];
+    deps[baz] = vec![merp, slerp];
+    assert_eq!(global_scope(exe, &[], &deps), vec![exe, foo, bar, baz, derp, slerp, merp]);
+}
+
+#[test]
+fn global_scope_preload_t () {
+    // preloads come before the executable's own dependencies, and their dependencies are just another level
+    let (exe, a, preload, q, r) = (0, 1, 2, 3, 4);
+    let mut deps = vec![vec![]; 5];
+    deps[exe] = vec![a];
+    deps[preload] = vec![q];
+    deps[a] = vec![q, r];
+    assert_eq!(global_scope(exe, &[preload], &deps), vec![exe, preload, a, q, r]);
+    // preloading something the executable needs anyway doesn't load it twice
+    assert_eq!(global_scope(exe, &[a], &deps), vec![exe, a, q, r]);
+}
+
+#[test]
+fn local_scope_t () {
+    let (exe, a, b, c, d) = (0, 1, 2, 3, 4);
+    let mut deps = vec![vec![]; 5];
+    deps[exe] = vec![a, b];
+    deps[a] = vec![c];
+    deps[b] = vec![d];
+    deps[c] = vec![a]; // cycles happen
+    assert_eq!(local_scope(a, &deps), vec![a, c]);
+    assert_eq!(local_scope(c, &deps), vec![c, a]);
+    assert_eq!(local_scope(d, &deps), vec![d]);
+    assert_eq!(local_scope(exe, &deps), vec![exe, a, b, c, d]);
+}
+
+#[test]
+fn init_order_t () {
+    // the example above again; whatever order the walk takes, every object has to come after everything it needs
+    let (exe, foo, bar, baz, derp, slerp, merp) = (0, 1, 2, 3, 4, 5, 6);
+    let mut deps = vec![vec![]; 7];
+    deps[exe] = vec![foo, bar, baz];
+    deps[bar] = vec![derp, baz, foo, slerp, merp];
+    deps[baz] = vec![merp, slerp];
+    let roots: Vec<usize> = (0..deps.len()).collect();
+    let order = init_order(&roots, &deps);
+    assert_eq!(order, vec![foo, derp, merp, slerp, baz, bar, exe]);
+    for (idx, needed) in deps.iter().enumerate() {
+        let pos = order.iter().position(|&i| i == idx).unwrap();
+        for dep in needed {
+            assert!(order.iter().position(|i| i == dep).unwrap() < pos);
+        }
+    }
+}
+
+#[test]
+fn unused_t () {
+    let (exe, a, b, c) = (0, 1, 2, 3);
+    let mut deps = vec![vec![]; 4];
+    deps[exe] = vec![a, b];
+    deps[a] = vec![c];
+    deps[b] = vec![c];
+    let mut bound: Vec<Bound> = (0..4).map(|_| Bound::default()).collect();
+    // the exe binds to b and c (which it never asked for), and to itself; a binds to c, and b to nothing at all
+    bound[exe].providers = vec![exe, b, c];
+    bound[a].providers = vec![c];
+    assert_eq!(unused(&deps, &bound), vec![(exe, a), (b, c)]);
+    // an object dlopen'd after we last looked has bound to nothing yet
+    assert_eq!(unused(&deps, &bound[..2]), vec![(exe, a), (b, c)]);
+}
+
+#[test]
+fn init_order_cycle_t () {
+    let (exe, a, b, c) = (0, 1, 2, 3);
+    let mut deps = vec![vec![]; 4];
+    deps[exe] = vec![a, c];
+    deps[a] = vec![b];
+    deps[b] = vec![a]; // the walk gets to b through a, so b goes first
+    assert_eq!(init_order(&[exe], &deps), vec![b, a, c, exe]);
+    // an object nothing reached from the roots is never constructed
+    assert_eq!(init_order(&[a], &deps), vec![b, a]);
+}
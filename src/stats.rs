@@ -0,0 +1,169 @@
+/// Startup statistics: how long each phase of linking took, and how many relocations and symbol lookups each object cost us,
+/// printed with `LD_DEBUG=statistics`, and handed to anyone benchmarking us through `dryad_get_stats`.
+///
+/// Times are in nanoseconds, from the monotonic clock, which we read with a raw syscall, since the first thing we time is relocating ourselves,
+/// when calling into libc (or even touching anything that needs a relocation) isn't an option yet.
+use std::ffi::CString;
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use libc;
+
+use linker::{self, Linker};
+use lock;
+
+/// How long each phase took, in nanoseconds
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Timings {
+    pub self_relocation: u64,
+    pub tls: u64,
+    /// Finding, mapping and parsing the program's dependencies (and the program itself, when we're run directly)
+    pub loading: u64,
+    pub relocate_got: u64,
+    /// Binding `JUMP_SLOT`s up front, not counting ifuncs
+    pub relocate_plt: u64,
+    /// Calling ifunc resolvers for `IRELATIVE`s
+    pub ifuncs: u64,
+    pub constructors: u64,
+    /// From entering the linker to handing control to the program; more than the sum of the above
+    pub total: u64,
+}
+
+/// What relocating an object cost
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Counts {
+    pub relative: usize,
+    /// Relocations against a symbol we found, i.e., `GLOB_DAT`, `JUMP_SLOT`s bound up front, and friends
+    pub symbolic: usize,
+    /// Symbol lookups through the scopes while relocating; lazy binding's are in `lazy_lookups`
+    pub lookups: usize,
+    pub ifuncs: usize,
+    pub ifunc_time: u64,
+}
+
+impl Counts {
+    pub fn add (&mut self, other: &Counts) {
+        self.relative += other.relative;
+        self.symbolic += other.symbolic;
+        self.lookups += other.lookups;
+        self.ifuncs += other.ifuncs;
+        self.ifunc_time += other.ifunc_time;
+    }
+}
+
+/// An object's counts, by link map index
+pub struct Object {
+    pub name: CString,
+    pub counts: Counts,
+}
+
+impl Object {
+    pub fn new (name: &str) -> Object {
+        Object { name: CString::new(name).unwrap_or_default(), counts: Counts::default() }
+    }
+}
+
+/// Every symbol `dryad_resolve_symbol` has looked up, i.e., every first call through a PLT slot (or every call, with `LD_BIND_NOT`)
+pub static LAZY_LOOKUPS: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// What `dryad_get_stats` fills in
+#[repr(C)]
+pub struct DryadStats {
+    pub timings: Timings,
+    /// Everything in `objects`, summed
+    pub totals: Counts,
+    pub lazy_lookups: usize,
+    pub objects: usize,
+}
+
+/// What `dryad_get_stats` fills in for each object, in link map order
+#[repr(C)]
+pub struct DryadObjectStats {
+    /// The object's name, valid for as long as the process lives
+    pub name: *const libc::c_char,
+    pub counts: Counts,
+}
+
+/// The kernel's `struct timespec`, whose fields are `long`s
+#[repr(C)]
+struct Timespec {
+    tv_sec: isize,
+    tv_nsec: isize,
+}
+
+const CLOCK_MONOTONIC: usize = 1;
+
+/// The monotonic clock, in nanoseconds; safe to call before we've relocated ourselves, since it's the raw syscall, which every arch has
+#[inline(always)]
+pub fn now () -> u64 {
+    let mut ts = Timespec { tv_sec: 0, tv_nsec: 0 };
+    unsafe { syscall!(CLOCK_GETTIME, CLOCK_MONOTONIC, &mut ts as *mut Timespec as usize); }
+    ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+}
+
+/// Sums every object's counts
+pub fn totals (objects: &[Object]) -> Counts {
+    let mut totals = Counts::default();
+    for object in objects {
+        totals.add(&object.counts);
+    }
+    totals
+}
+
+fn percent (part: u64, total: u64) -> f64 {
+    if total == 0 { 0.0 } else { part as f64 * 100.0 / total as f64 }
+}
+
+/// `LD_DEBUG=statistics`, more or less in glibc's format, except our times are in nanoseconds instead of cycles
+pub fn print (timings: &Timings, objects: &[Object]) {
+    let totals = totals(objects);
+    let relocation = timings.relocate_got + timings.relocate_plt + timings.ifuncs;
+    dbg!(STATISTICS, "runtime linker statistics:\n  total startup time in dynamic loader: {} ns", timings.total);
+    for &(what, time) in [("time needed for self relocation", timings.self_relocation),
+                          ("time needed for tls setup", timings.tls),
+                          ("time needed to load objects", timings.loading),
+                          ("time needed for relocation", relocation),
+                          ("of which relocating GOTs", timings.relocate_got),
+                          ("binding PLTs", timings.relocate_plt),
+                          ("calling ifunc resolvers", timings.ifuncs),
+                          ("time needed for constructors", timings.constructors)].iter() {
+        dbg!(STATISTICS, "{:>38}: {} ns ({:.1}%)", what, time, percent(time, timings.total));
+    }
+    dbg!(STATISTICS, "{:>38}: {}", "number of relocations", totals.symbolic);
+    dbg!(STATISTICS, "{:>38}: {}", "number of relative relocations", totals.relative);
+    dbg!(STATISTICS, "{:>38}: {}", "number of symbol lookups", totals.lookups);
+    dbg!(STATISTICS, "{:>38}: {}", "number of ifuncs", totals.ifuncs);
+    dbg!(STATISTICS, "{:>38}: {}", "number of objects", objects.len());
+    for object in objects {
+        let c = &object.counts;
+        dbg!(STATISTICS, "  {}: {} relative, {} symbolic, {} lookups, {} ifuncs ({} ns)", object.name.to_string_lossy(), c.relative, c.symbolic, c.lookups, c.ifuncs, c.ifunc_time);
+    }
+}
+
+/// Fills in `stats` (if it isn't null) with how long we took to start the program and what relocating everything has cost so far,
+/// and the first `count` entries of `objects` with each object's share, in link map order.
+/// Returns the number of objects in the link map, so a caller can ask for the count first, with a null `objects`, and then allocate.
+#[no_mangle]
+pub unsafe extern fn dryad_get_stats (stats: *mut DryadStats, objects: *mut DryadObjectStats, count: libc::size_t) -> libc::size_t {
+    let _guard = lock::LINK_LOCK.lock();
+    if linker::LINKER.is_null() {
+        return 0
+    }
+    let linker = &*(linker::LINKER as *const Linker<'static>);
+    let (timings, per_object) = linker.stats();
+    if !stats.is_null() {
+        *stats = DryadStats {
+            timings: *timings,
+            totals: totals(per_object),
+            lazy_lookups: LAZY_LOOKUPS.load(Ordering::Relaxed),
+            objects: per_object.len(),
+        };
+    }
+    if !objects.is_null() {
+        for (i, object) in per_object.iter().take(count).enumerate() {
+            ptr::write(objects.offset(i as isize), DryadObjectStats { name: object.name.as_ptr(), counts: object.counts });
+        }
+    }
+    per_object.len()
+}
@@ -1,7 +1,11 @@
 use libc;
 use std::cmp;
+use std::mem;
 use std::ptr;
+use std::sync::atomic::Ordering;
 use elf::program_header;
+use linker::{self, Linker};
+use lock;
 
 #[allow(non_camel_case_types)]
 #[derive(Debug)]
@@ -143,18 +147,18 @@ unsafe fn install_dtv(tls_storage: *mut libc::c_void, dtv_head: *mut DtvHead) {
 /// dl-tls.c:313 allocate_dtv (void *result)
 unsafe fn allocate_dtv (max_dtv_idx: u32, tls_storage: *mut libc::c_void) -> *mut libc::c_void {
     let dtv_len = max_dtv_idx as usize + DTV_SURPLUS;
-    dbgc!(purple_bold: true, "tls", "allocate_dtv dtv_len: {:?}", dtv_len);
+    dbgc!(purple_bold: TLS, "tls", "allocate_dtv dtv_len: {:?}", dtv_len);
     let dtv = libc::calloc(dtv_len + 2, SIZEOF_DTV as libc::size_t) as *mut DtvHead;
-    dbgc!(purple_bold: true, "tls", "allocate_dtv calloc dtv: {:?}", dtv);
+    dbgc!(purple_bold: TLS, "tls", "allocate_dtv calloc dtv: {:?}", dtv);
     if !dtv.is_null() {
         // hehe surries!
         (*dtv).counter = dtv_len;
-        dbgc!(purple_bold: true, "tls", "allocate_dtv calloc dtv: {:?}", *dtv);
+        dbgc!(purple_bold: TLS, "tls", "allocate_dtv calloc dtv: {:?}", *dtv);
         install_dtv(tls_storage, dtv);
-        dbgc!(purple_bold: true, "tls", "allocate_dtv calloc post install: {:?} dtv: {:?}", **(tls_storage.offset(8) as *mut *mut Dtv), *dtv);
+        dbgc!(purple_bold: TLS, "tls", "allocate_dtv calloc post install: {:?} dtv: {:?}", **(tls_storage.offset(8) as *mut *mut Dtv), *dtv);
         tls_storage
     } else {
-        dbgc!(purple_bold: true, "tls", "allocate_dtv dtv is NULL");
+        dbgc!(purple_bold: TLS, "tls", "allocate_dtv dtv is NULL");
         ptr::null_mut::<libc::c_void>()
     }
 }
@@ -162,12 +166,12 @@ unsafe fn allocate_dtv (max_dtv_idx: u32, tls_storage: *mut libc::c_void) -> *mu
 pub unsafe fn _dl_allocate_tls_storage (max_dtv_idx: u32, static_align: usize, static_size: usize, static_used: usize) -> *mut libc::c_void {
     // when DTV_AT_TP need to adjust static_size
     let mut result = libc::memalign(static_align, static_size);
-    dbgc!(purple_bold: true, "tls", "_dl_allocate_tls_storage result: {:?}", result);
+    dbgc!(purple_bold: TLS, "tls", "_dl_allocate_tls_storage result: {:?}", result);
     let allocated = result; // to be used by free in case fails, unimplemented
     result = result.offset(static_size as isize - TLS_TCB_SIZE as isize);
-    dbgc!(purple_bold: true, "tls", "_dl_allocate_tls_storage post result: {:?}", result);
+    dbgc!(purple_bold: TLS, "tls", "_dl_allocate_tls_storage post result: {:?}", result);
     memset(result as *mut u8, 0x0, TLS_TCB_SIZE);
-    dbgc!(purple_bold: true, "tls", "_dl_allocate_tls_storage memset result: {:?}", result);
+    dbgc!(purple_bold: TLS, "tls", "_dl_allocate_tls_storage memset result: {:?}", result);
     allocate_dtv(max_dtv_idx, result)
 }
 
@@ -186,7 +190,7 @@ pub unsafe fn allocate_tls_init (max_dtv_idx: u32, tls_storage: *mut libc::c_voi
     // and then call install_dtv
     let mut total = 0;
     let mut maxgen = 0;
-    dbgc!(purple_bold: true, "tls", "allocate_tls entering loop with modules: {:?}", modules);
+    dbgc!(purple_bold: TLS, "tls", "allocate_tls entering loop with modules: {:?}", modules);
     // TODO: fix this, broken w.r.t. glibc implementation because
     // slotinfo_list is a linked list of slotinfo_list, with len, next, and slotinfo[]; but we're just using the slotinfo[] here, because for simple test programs the linked list is always one element...
     loop {
@@ -194,10 +198,10 @@ pub unsafe fn allocate_tls_init (max_dtv_idx: u32, tls_storage: *mut libc::c_voi
         // TODO: this is broken here too with loop condition
         for cnt in 0..modules.len() {
             if total + cnt > max_dtv_idx as usize {
-                dbgc!(purple_bold: true, "tls", "reached break total + cnt = {} > max_dtv_idx {}", total + cnt, max_dtv_idx);
+                dbgc!(purple_bold: TLS, "tls", "reached break total + cnt = {} > max_dtv_idx {}", total + cnt, max_dtv_idx);
                 break;
             }
-            dbgc!(purple_bold: true, "tls", "allocate_tls cnt: {:?} {:?}", cnt, TLS_DTV_UNALLOCATED);
+            dbgc!(purple_bold: TLS, "tls", "allocate_tls cnt: {:?} {:?}", cnt, TLS_DTV_UNALLOCATED);
             let info = modules[cnt].info;
             maxgen = cmp::max(maxgen, modules[cnt].generation);
             let mut dtv_ = dtv.offset(info.modid as isize) as *mut Dtv;
@@ -206,20 +210,20 @@ pub unsafe fn allocate_tls_init (max_dtv_idx: u32, tls_storage: *mut libc::c_voi
             // cfg TLS_TCB_AT_TP
             let dest = (tls_storage.offset(-info.offset as isize)) as *mut u8;
             let size = info.blocksize - info.image_size;
-            dbgc!(purple_bold: true, "tls", "memset + memcpy: {:?} with size: {} and info.image 0x{:x} info.image_size {}", dest, size, info.image, info.image_size);
+            dbgc!(purple_bold: TLS, "tls", "memset + memcpy: {:?} with size: {} and info.image 0x{:x} info.image_size {}", dest, size, info.image, info.image_size);
             ptr::copy_nonoverlapping(dest, info.image as *mut u8, info.image_size);
 //            libc::memcpy(dest, info.image, info.image_size);
             memset(dest, 0u8, size);
         }
 
         total += cnt;
-        dbgc!(purple_bold: true, "tls", "allocate_tls new total: {:?}", total);
+        dbgc!(purple_bold: TLS, "tls", "allocate_tls new total: {:?}", total);
         if total >= max_dtv_idx as usize {
             break;
         }
     }
 
-    dbgc!(purple_bold: true, "tls", "allocate_tls finished, setting dtv head to maxgen {} with tls storage: {:?}", maxgen, tls_storage);
+    dbgc!(purple_bold: TLS, "tls", "allocate_tls finished, setting dtv head to maxgen {} with tls storage: {:?}", maxgen, tls_storage);
     // haven't figured this out; seems like a bug in glibc, but that would be impossible, right?
     // dtv[0].counter = maxgen
     // but dtv[-1] is the counter union ?
@@ -273,7 +277,7 @@ fn determine_offset(static_align: &mut usize, static_used: &mut usize, static_si
 
     *static_align = max_align;
 
-//    dbgc!(purple_bold: true, "tls", "determine_offset final static_used: {} static_size: {} static_align: {} freetop: {} freebottom: {}", static_used, static_size, static_align, freetop, freebottom);
+//    dbgc!(purple_bold: TLS, "tls", "determine_offset final static_used: {} static_size: {} static_align: {} freetop: {} freebottom: {}", static_used, static_size, static_align, freetop, freebottom);
 }
 
 // seeing segfault, notice shift of address when
@@ -306,19 +310,19 @@ pub unsafe fn init_tls(max_dtv_idx: u32, modules: &mut [SlotInfo]) {
     let mut static_size = 0;
     let mut static_used = 0;
 
-    dbgc!(purple_bold: true, "tls", "init_tls");
+    dbgc!(purple_bold: TLS, "tls", "init_tls");
     determine_offset(&mut static_align, &mut static_used, &mut static_size, modules);
 
-    dbgc!(purple_bold: true, "tls", "init_tls determine_offset {} {} {}", static_align, static_size, static_used);
+    dbgc!(purple_bold: TLS, "tls", "init_tls determine_offset {} {} {}", static_align, static_size, static_used);
 
     let tcbp = _dl_allocate_tls_storage(max_dtv_idx, static_align, static_size, static_used);
-    dbgc!(purple_bold: true, "tls", "init_tls allocating tls storage {:?}", tcbp);
+    dbgc!(purple_bold: TLS, "tls", "init_tls allocating tls storage {:?}", tcbp);
     // make the syscall
     //tls_init_tp(tcbp);
-    dbgc!(purple_bold: true, "tls", "init_tls installed into thread");
+    dbgc!(purple_bold: TLS, "tls", "init_tls installed into thread");
     // glibc sets global tls_init_tp_called = true now and returns the tcbp for later use in allocate_tls_init(tcbp);
     allocate_tls_init(max_dtv_idx, tcbp, modules);
-    dbgc!(purple_bold: true, "tls", "init_tls allocate_tls_init call finished");
+    dbgc!(purple_bold: TLS, "tls", "init_tls allocate_tls_init call finished");
     tls_init_tp(tcbp);
 }
 
@@ -347,17 +351,117 @@ pub const TLS_STATIC_SURPLUS: libc::size_t = 64 + DL_NNS * 100;
 
 pub const TLS_DTV_UNALLOCATED: *mut libc::c_void = !0usize as *mut libc::c_void;
 
+// thread_local destructors
+// C++ `thread_local` objects with non-trivial destructors (and rust's `thread_local!`) register their destructor with `__cxa_thread_atexit_impl (func, obj, dso_symbol)`,
+// where `dso_symbol` is the registering object's `__dso_handle`, and the object has to stay mapped until every thread which registered one has run it.
+// glibc defines it in libc.so.6 and keeps the count in ld.so's private part of `struct link_map`, which we don't have; libc comes before us in the global scope, so there ours is never called.
+// musl leaves it out, and libstdc++ and rust's std only look it up weakly, falling back to a pthread key of their own which pins nothing; with dryad they get ours instead,
+// which counts each destructor against its object's `tls_dtors` until it has run.  There's no dlclose yet, but a pinned object is what it (and `Linker::unwind`) must leave mapped.
+
+/// A `thread_local` destructor, and the `__dso_handle` of whoever registered it, which finds the object to unpin once it has run
+struct TlsDtor {
+    func: extern fn (*mut libc::c_void),
+    obj: *mut libc::c_void,
+    dso_symbol: usize,
+}
+
+type PthreadKey = libc::c_uint;
+type PthreadKeyCreateFn = extern fn (key: *mut PthreadKey, destructor: extern fn (*mut libc::c_void)) -> libc::c_int;
+type PthreadGetspecificFn = extern fn (key: PthreadKey) -> *mut libc::c_void;
+type PthreadSetspecificFn = extern fn (key: PthreadKey, value: *const libc::c_void) -> libc::c_int;
+
+/// Each thread's destructors hang off this key, in the program's libc (our own musl's threads aren't the program's), which hands them to `thread_exit` when the thread exits.
+/// Created by the first registration, under the link lock
+static mut DTOR_KEY: Option<(PthreadKey, PthreadGetspecificFn, PthreadSetspecificFn)> = None;
+
+unsafe fn dtor_key (linker: &Linker) -> Option<(PthreadKey, PthreadGetspecificFn, PthreadSetspecificFn)> {
+    if DTOR_KEY.is_none() {
+        // handle 1 is the program, whose scope is everything it was linked against
+        let (create, get, set) = match (linker.dlsym(1, "pthread_key_create"), linker.dlsym(1, "pthread_getspecific"), linker.dlsym(1, "pthread_setspecific")) {
+            (Ok (create), Ok (get), Ok (set)) => (create, get, set),
+            _ => {
+                dbgc!(purple_bold: TLS, "tls", "no pthread_key_create, pthread_getspecific or pthread_setspecific in scope; can't register thread_local destructors");
+                return None
+            }
+        };
+        let create = mem::transmute::<usize, PthreadKeyCreateFn>(create);
+        let mut key = 0;
+        if create(&mut key, thread_exit) != 0 {
+            dbgc!(purple_bold: TLS, "tls", "pthread_key_create failed; can't register thread_local destructors");
+            return None
+        }
+        DTOR_KEY = Some ((key, mem::transmute::<usize, PthreadGetspecificFn>(get), mem::transmute::<usize, PthreadSetspecificFn>(set)));
+    }
+    DTOR_KEY
+}
+
+/// Registers `func(obj)` to run when the calling thread exits (or, for the main thread, at exit), pinning the object `dso_symbol` is in until it has; returns non-zero if it can't
+#[no_mangle]
+pub unsafe extern fn __cxa_thread_atexit_impl (func: extern fn (*mut libc::c_void), obj: *mut libc::c_void, dso_symbol: *const libc::c_void) -> libc::c_int {
+    let _guard = lock::LINK_LOCK.lock();
+    if linker::LINKER.is_null() {
+        return -1
+    }
+    let linker = &*linker::LINKER;
+    let (key, getspecific, setspecific) = match dtor_key(linker) { Some (key) => key, None => return -1 };
+    let mut dtors = getspecific(key) as *mut Vec<TlsDtor>;
+    if dtors.is_null() {
+        dtors = Box::into_raw(Box::new(Vec::new()));
+        if setspecific(key, dtors as *const libc::c_void) != 0 {
+            drop(Box::from_raw(dtors));
+            return -1
+        }
+    }
+    if let Some(so) = linker.object_containing(dso_symbol as usize) {
+        let pending = so.tls_dtors.fetch_add(1, Ordering::AcqRel) + 1;
+        dbgc!(purple_bold: TLS, "tls", "registered thread_local destructor 0x{:x} in {}, {} pending", func as usize, so.name(), pending);
+    }
+    (*dtors).push(TlsDtor { func: func, obj: obj, dso_symbol: dso_symbol as usize });
+    0
+}
+
+/// The key's destructor: libc calls this with an exiting thread's destructors (and again, if running them registered more)
+extern fn thread_exit (dtors: *mut libc::c_void) {
+    unsafe { run_dtors(Box::from_raw(dtors as *mut Vec<TlsDtor>)) }
+}
+
+/// Runs `dtors`, last registered first, unpinning each one's object as we go
+unsafe fn run_dtors (dtors: Box<Vec<TlsDtor>>) {
+    for dtor in dtors.into_iter().rev() {
+        (dtor.func)(dtor.obj);
+        let _guard = lock::LINK_LOCK.lock();
+        if let Some(so) = linker::LINKER.as_ref().and_then(|linker| linker.object_containing(dtor.dso_symbol)) {
+            so.tls_dtors.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+}
+
+/// The main thread doesn't exit as far as pthread keys are concerned, so `_dryad_fini` runs its destructors at exit, before any object's own, like glibc's `__call_tls_dtors`
+pub fn run_main_thread_dtors () {
+    unsafe {
+        let (key, getspecific, setspecific) = match DTOR_KEY { Some (key) => key, None => return };
+        // a destructor may register another
+        loop {
+            let dtors = getspecific(key) as *mut Vec<TlsDtor>;
+            if dtors.is_null() {
+                break
+            }
+            setspecific(key, ptr::null());
+            run_dtors(Box::from_raw(dtors));
+        }
+    }
+}
+
 // TODO: eventually replace musl's init_tls call with Lachesis and use it for our implementation
 /// https://en.wikipedia.org/wiki/Lachesis_(mythology)
 pub struct Lachesis {
     pub modules: Vec<SlotInfo>,
     pub current_modid: u32,
-    debug: bool,
 }
 
 impl Lachesis {
-    pub fn new(debug: bool) -> Lachesis {
-        Lachesis { modules: Vec::with_capacity(3), current_modid: 0, debug: debug }
+    pub fn new() -> Lachesis {
+        Lachesis { modules: Vec::with_capacity(3), current_modid: 0 }
     }
 
     pub unsafe fn init_from_phdrs(bias: usize, phdrs: &[program_header::ProgramHeader])  {
@@ -370,7 +474,7 @@ impl Lachesis {
                 let mut static_used = 0;
                 let mut modules = vec![SlotInfo { generation: 0, info: tls }];
                 ::utils::write("mod\n");
-                dbgc!(purple_bold: true, "tls", "init_tls");
+                dbgc!(purple_bold: TLS, "tls", "init_tls");
                 determine_offset(&mut static_align, &mut static_used, &mut static_size, &mut modules);
                 ::utils::write("offset: ");
                 ::utils::write("\nalign: ");
@@ -381,7 +485,7 @@ impl Lachesis {
                 ::utils::write_u64(static_used as u64, false);
                 ::utils::write("\n");
                 let tcbp = _dl_allocate_tls_storage(1, static_align, static_size, static_used);
-                dbgc!(purple_bold: true, "tls", "init_tls allocating tls storage {:?}", tcbp);
+                dbgc!(purple_bold: TLS, "tls", "init_tls allocating tls storage {:?}", tcbp);
                 ::utils::write("allocate tls storage: 0x");
                 ::utils::write_u64(tcbp as u64, true);
                 ::utils::write("\n");
@@ -390,7 +494,7 @@ impl Lachesis {
                 ::utils::write("init tp\n");
                 allocate_tls_init(1, tcbp, &modules);
                 ::utils::write("allocate tls init\n");
-                dbgc!(purple_bold: true, "tls", "init_tls allocate_tls_init call finished");
+                dbgc!(purple_bold: TLS, "tls", "init_tls allocate_tls_init call finished");
 
             }
         }
@@ -400,7 +504,7 @@ impl Lachesis {
     pub fn push_module(&mut self, soname: &str, bias: usize, phdr: &program_header::ProgramHeader) -> TlsInfo {
         let modid = { self.current_modid += 1; self.current_modid }; // increment, this will probably need to be atomic
         let tls = TlsInfo::new(modid, bias, phdr);
-        dbgc!(purple_bold: self.debug, "lachesis", "PT_TLS in {} with {:?}", soname, tls);
+        dbgc!(purple_bold: TLS, "lachesis", "PT_TLS in {} with {:?}", soname, tls);
         self.modules.push(SlotInfo { generation: 1, info: tls });
         tls
     }
@@ -0,0 +1,106 @@
+/// `LD_DRYAD_TRACE=file`: a timeline of where startup (and lazy binding, and teardown) time goes, in Chrome's trace event format,
+/// written to `file.<pid>` (like `LD_DEBUG_OUTPUT`, so a program that forks gets a timeline per process, instead of each exit overwriting the last), for `chrome://tracing` or Perfetto.
+/// A forked child's starts with everything its parent recorded before the fork.
+///
+/// Every span is a complete (`"ph": "X"`) event: opening each object, mapping it, and parsing it, each of its relocation passes, each constructor and destructor,
+/// and each lazy binding.  Events are only buffered while the program runs, and written out in `_dryad_fini`, so tracing costs a clock read and a push,
+/// not a write, per span; anything the program does after `exit` starts, or if it never calls it, isn't there.
+///
+/// The clock is `stats::now`, so spans line up with `LD_DEBUG=statistics`.
+use std::fs::File;
+use std::io::Write;
+use libc;
+
+use dump;
+use lock;
+use stats;
+use utils;
+
+struct Event {
+    category: &'static str,
+    name: String,
+    start: u64,
+    end: u64,
+    tid: usize,
+}
+
+/// Where to write the events, less the `.<pid>`, if we're tracing
+static mut PATH: Option<String> = None;
+/// Only touch with the link lock held; lazy binding, `dlopen` and destructors can all come from any thread
+static mut EVENTS: Option<Vec<Event>> = None;
+
+/// Starts tracing, to be written to `path` at exit; only call this once we've relocated ourselves
+pub fn init (path: &str) {
+    unsafe {
+        PATH = Some (path.to_string());
+        // enough for a few hundred libraries without reallocating in the middle of something we're timing
+        EVENTS = Some (Vec::with_capacity(4096));
+    }
+}
+
+#[inline(always)]
+pub fn enabled () -> bool {
+    unsafe { PATH.is_some() }
+}
+
+/// When a span starts; free when we're not tracing
+#[inline(always)]
+pub fn start () -> u64 {
+    if enabled() { stats::now() } else { 0 }
+}
+
+/// Records a span called `name` from `start` (see `start`) until now
+#[inline(always)]
+pub fn span (category: &'static str, name: &str, start: u64) {
+    if enabled() {
+        record(category, name, start, stats::now());
+    }
+}
+
+/// Records a span called `name` from `start` to `end`, e.g., something that happened before we could trace it
+pub fn record (category: &'static str, name: &str, start: u64, end: u64) {
+    if !enabled() {
+        return
+    }
+    let _guard = lock::LINK_LOCK.lock();
+    unsafe {
+        if let Some(ref mut events) = EVENTS {
+            events.push(Event { category: category, name: name.to_string(), start: start, end: end, tid: lock::gettid() });
+        }
+    }
+}
+
+/// Nanoseconds as the microseconds the format wants
+fn micros (ns: u64) -> String {
+    format!("{}.{:03}", ns / 1000, ns % 1000)
+}
+
+#[test]
+fn micros_t () {
+    assert_eq!(micros(0), "0.000");
+    assert_eq!(micros(1234567), "1234.567");
+    assert_eq!(micros(1000), "1.000");
+}
+
+/// Writes everything recorded so far to `path.<pid>`, and stops tracing
+pub fn flush () {
+    let _guard = lock::LINK_LOCK.lock();
+    let (path, events) = unsafe {
+        match (PATH.take(), EVENTS.take()) {
+            (Some (path), Some (events)) => (path, events),
+            _ => return,
+        }
+    };
+    // now, not at `init`, since we may be a forked child
+    let pid = unsafe { libc::getpid() };
+    let path = format!("{}.{}", path, pid);
+    let events: Vec<String> = events.iter().map(|event| {
+        format!("  {{\"name\": \"{} {}\", \"cat\": \"{}\", \"ph\": \"X\", \"ts\": {}, \"dur\": {}, \"pid\": {}, \"tid\": {}}}",
+                event.category, dump::escape(&event.name), event.category, micros(event.start), micros(event.end.saturating_sub(event.start)), pid, event.tid)
+    }).collect();
+    let trace = format!("{{\"traceEvents\": [\n{}\n], \"displayTimeUnit\": \"ns\"}}\n", events.join(",\n"));
+    let written = File::create(&path).and_then(|mut file| file.write_all(trace.as_bytes()));
+    if let Err(e) = written {
+        utils::write_err(&format!("dryad: warning: cannot write trace to {}: {}\n", path, e));
+    }
+}
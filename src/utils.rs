@@ -18,33 +18,25 @@ macro_rules! bracket {
        ( concat!(colour!(white_bold2: "<"), colour!($c: $str), colour!(white_bold2: ">")) )
 }
 
+/// Prints to the `LD_DEBUG` output if `$cat` (one of the categories in `debug`, e.g., `LIBS`) is on
 macro_rules! dbg {
-    ($dbg:expr, $fmt:expr) =>
-        (if $dbg {
-        ( print!(
-            concat!(bracket!(green: "dryad"), " ", $fmt, "\n")))
-        });
-    ($dbg:expr, $fmt:expr, $($arg:tt)*) =>
-        (if $dbg {
-        ( print!(
-            concat!(bracket!(green: "dryad"), " ", $fmt, "\n"),
-            $($arg)*) );
-        });
+    ($cat:ident, $fmt:expr) =>
+        (dbgc!(green: $cat, "dryad", $fmt));
+    ($cat:ident, $fmt:expr, $($arg:tt)*) =>
+        (dbgc!(green: $cat, "dryad", $fmt, $($arg)*));
 }
 
+/// Like `dbg!` with a `$prefix` of your own, in colour `$c` when the output is a terminal
 macro_rules! dbgc {
-    ($c:ident: $dbg:expr, $prefix:expr, $fmt:expr) =>
-        ( if $dbg {
-            ( print!(
-                concat!(bracket!($c: $prefix), " ", $fmt, "\n")) )
+    ($c:ident: $cat:ident, $prefix:expr, $fmt:expr) =>
+        ( if ::debug::enabled(::debug::$cat) {
+            ::debug::print(if ::debug::color() { bracket!($c: $prefix) } else { concat!("<", $prefix, ">") }, format_args!($fmt));
         });
 
-    ($c:ident: $dbg:expr, $prefix:expr, $fmt:expr, $($arg:tt)*) =>
-        ( if $dbg {
-            ( print!(
-                concat!(bracket!($c: $prefix), " ", $fmt, "\n"),
-                $($arg)*) );
-        } );
+    ($c:ident: $cat:ident, $prefix:expr, $fmt:expr, $($arg:tt)*) =>
+        ( if ::debug::enabled(::debug::$cat) {
+            ::debug::print(if ::debug::color() { bracket!($c: $prefix) } else { concat!("<", $prefix, ">") }, format_args!($fmt, $($arg)*));
+        });
 }
 
 
@@ -81,6 +73,12 @@ pub fn write(msg: &str){
     asm_write(msg.as_ptr(), msg.len() as u64);
 }
 
+/// `write`, but to stderr, for errors and warnings that shouldn't end up in the program's (or ldd's) output.
+/// It goes through libc, so only once we're relocated
+pub fn write_err(msg: &str){
+    unsafe { ::libc::write(2, msg.as_ptr() as *const ::libc::c_void, msg.len()); }
+}
+
 fn digit_to_char_code(i: u8) -> u8 {
     if i <= 9 {
         i + 48
@@ -189,7 +187,8 @@ pub fn get_errno () -> i32 {
 /// **NB**: Make sure this is called _after_ relocation, since we need to allocate the closure on the heap
 pub fn set_panic () {
     ::std::panic::set_hook(Box::new(|panic| {
-        dbgc!(orange_bold: true, "dryad.panic", r#"Thamus, are you there? When you reach Palodes, take care to proclaim that the great god Pan is dead."#);
+        // whatever LD_DEBUG says
+        ::debug::print(bracket!(orange_bold: "dryad.panic"), format_args!(r#"Thamus, are you there? When you reach Palodes, take care to proclaim that the great god Pan is dead."#));
         if let Some(location) = panic.location() {
             println!("-=|dryad====- died in {}:{}", location.file(), location.line());
         }
@@ -202,7 +201,7 @@ pub fn set_panic () {
                 None => "Box<Any>",
             }
         };
-        dbgc!(orange_bold: true, "dryad.panic", "Died because {}", msg);
+        ::debug::print(bracket!(orange_bold: "dryad.panic"), format_args!("Died because {}", msg));
         _exit(1);
     }));
 }
@@ -268,6 +267,7 @@ pub mod mmap {
     extern {
         fn mmap64(addr: *const usize, len: usize, prot: isize, flags: libc::c_int, fildes: libc::c_int, off: usize) -> usize;
         fn mprotect(addr: *const libc::c_void, len: libc::size_t, prot: libc::c_int) -> libc::c_int;
+        fn munmap(addr: *const libc::c_void, len: libc::size_t) -> libc::c_int;
     }
 
     #[inline(always)]
@@ -275,6 +275,11 @@ pub mod mmap {
         mmap64(addr, len, prot, flags, fildes, off)
     }
 
+    #[inline(always)]
+    pub unsafe fn unmap(addr: *const usize, len: usize) -> libc::c_int {
+        munmap(addr as *const libc::c_void, len as libc::size_t)
+    }
+
     #[inline(always)]
     pub fn pflags_to_prot (x: u32) -> isize {
         use elf::program_header::{PF_X, PF_R, PF_W};
@@ -286,6 +291,14 @@ pub mod mmap {
         (if x & PF_W == PF_W { PROT_WRITE } else { 0 })
     }
 
+    /// mprotects the pages spanning `[start, end)`
+    #[inline(always)]
+    pub fn protect (start: usize, end: usize, prot: isize) -> bool {
+        let start = page::page_start(start);
+        let end = page::page_end(end);
+        unsafe { mprotect(start as *const libc::c_void, (end - start) as libc::size_t, prot as libc::c_int) >= 0 }
+    }
+
     pub fn mprotect_phdrs (phdrs: &[program_header::ProgramHeader], bias: usize, flags: isize) -> bool {
         for phdr in phdrs {
             if phdr.p_type == program_header::PT_LOAD {
@@ -299,7 +312,7 @@ pub mod mmap {
                 let new_flags = prot | flags;
                 // avoid the syscall unless we're restoring the program header flags (flags == 0)
                 if flags != 0 && new_flags == prot {
-                    return true;
+                    continue;
                 }
                 let ret = unsafe { mprotect(seg_page_start as *const libc::c_void, (seg_page_end - seg_page_start) as libc::size_t, new_flags as libc::c_int) };
                 if ret < 0 { return false }
@@ -308,6 +321,20 @@ pub mod mmap {
         true
     }
 
+    /// Puts every `PT_LOAD` back to the permissions its `p_flags` asked for, e.g., after writing text relocations
+    pub fn restore_phdrs (phdrs: &[program_header::ProgramHeader], bias: usize) -> bool {
+        for phdr in phdrs {
+            if phdr.p_type == program_header::PT_LOAD {
+                let seg_start = phdr.p_vaddr as usize + bias;
+                let seg_end = seg_start + phdr.p_memsz as usize;
+                if !protect(seg_start, seg_end, pflags_to_prot(phdr.p_flags)) {
+                    return false
+                }
+            }
+        }
+        true
+    }
+
     #[inline(always)]
     fn map_fragment(fd: &File, base: usize, offset: usize, size: usize) -> Result<(usize, usize, *const usize), String> {
         use utils::page;
@@ -0,0 +1,198 @@
+/// Symbol versioning, i.e., `DT_VERSYM`, `DT_VERNEED` and `DT_VERDEF`.
+///
+/// Every dynamic symbol has a parallel 16-bit entry in the `.gnu.version` (`DT_VERSYM`) table; for an undefined symbol it names
+/// an entry in the requesting object's `.gnu.version_r` (`DT_VERNEED`) table, e.g., `memcpy@GLIBC_2.14`, and for a defined symbol it names
+/// an entry in the providing object's `.gnu.version_d` (`DT_VERDEF`) table.  A binding is only valid if the two agree on the version string.
+use elf::dyn::Dyn;
+use goblin::strtab::Strtab;
+
+pub const DT_VERSYM: u64 = 0x6ffffff0;
+pub const DT_VERDEF: u64 = 0x6ffffffc;
+pub const DT_VERDEFNUM: u64 = 0x6ffffffd;
+pub const DT_VERNEED: u64 = 0x6ffffffe;
+pub const DT_VERNEEDNUM: u64 = 0x6fffffff;
+
+/// Symbol is local, i.e., not available outside the object
+pub const VER_NDX_LOCAL: u16 = 0;
+/// Symbol is unversioned/in the base definition
+pub const VER_NDX_GLOBAL: u16 = 1;
+/// Set in a versym entry if the symbol is a non-default version (`foo@VERS` instead of `foo@@VERS`)
+pub const VERSYM_HIDDEN: u16 = 0x8000;
+pub const VERSYM_VERSION: u16 = 0x7fff;
+/// The version definition of the file itself, i.e., its soname
+pub const VER_FLG_BASE: u16 = 0x1;
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct Verdef {
+    pub vd_version: u16,
+    pub vd_flags: u16,
+    pub vd_ndx: u16,
+    pub vd_cnt: u16,
+    pub vd_hash: u32,
+    pub vd_aux: u32,
+    pub vd_next: u32,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct Verdaux {
+    pub vda_name: u32,
+    pub vda_next: u32,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct Verneed {
+    pub vn_version: u16,
+    pub vn_cnt: u16,
+    pub vn_file: u32,
+    pub vn_aux: u32,
+    pub vn_next: u32,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct Vernaux {
+    pub vna_hash: u32,
+    pub vna_flags: u16,
+    pub vna_other: u16,
+    pub vna_name: u32,
+    pub vna_next: u32,
+}
+
+/// A version a symbol reference asks for, e.g., `GLIBC_2.14`
+#[derive(Debug, Clone, Copy)]
+pub struct Version<'a> {
+    pub name: &'a str,
+    pub hash: u32,
+}
+
+/// The (biased) addresses of an object's version tables; zero means the table is absent
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VersionInfo {
+    pub versym: usize,
+    pub verdef: usize,
+    pub verdefnum: usize,
+    pub verneed: usize,
+    pub verneednum: usize,
+}
+
+impl VersionInfo {
+    pub fn new (dynamic: &[Dyn], bias: usize) -> VersionInfo {
+        let mut info = VersionInfo::default();
+        for dyn in dynamic {
+            match dyn.d_tag as u64 {
+                DT_VERSYM => info.versym = (dyn.d_val as usize).wrapping_add(bias),
+                DT_VERDEF => info.verdef = (dyn.d_val as usize).wrapping_add(bias),
+                DT_VERDEFNUM => info.verdefnum = dyn.d_val as usize,
+                DT_VERNEED => info.verneed = (dyn.d_val as usize).wrapping_add(bias),
+                DT_VERNEEDNUM => info.verneednum = dyn.d_val as usize,
+                _ => (),
+            }
+        }
+        info
+    }
+
+    /// Checks every version table lies within memory `mapped` says is mapped, and every name in them is a valid index into a strtab of `strsz` bytes,
+    /// so `requested` and `matches` never follow a bad offset
+    pub unsafe fn check<F: Fn(usize, usize) -> bool> (&self, num_syms: usize, strsz: usize, mapped: F) -> Result<(), String> {
+        if self.versym != 0 && !mapped(self.versym, num_syms * ::std::mem::size_of::<u16>()) {
+            return Err ("DT_VERSYM is not within a loaded segment".to_string())
+        }
+        let mut need = self.verneed;
+        for _ in 0..self.verneednum {
+            if !mapped(need, ::std::mem::size_of::<Verneed>()) {
+                return Err ("DT_VERNEED entry is not within a loaded segment".to_string())
+            }
+            let verneed = &*(need as *const Verneed);
+            if verneed.vn_file as usize >= strsz {
+                return Err ("DT_VERNEED file name is past the end of the strtab".to_string())
+            }
+            let mut aux = need.wrapping_add(verneed.vn_aux as usize);
+            for _ in 0..verneed.vn_cnt {
+                if !mapped(aux, ::std::mem::size_of::<Vernaux>()) {
+                    return Err ("DT_VERNEED auxiliary entry is not within a loaded segment".to_string())
+                }
+                let vernaux = &*(aux as *const Vernaux);
+                if vernaux.vna_name as usize >= strsz {
+                    return Err ("DT_VERNEED version name is past the end of the strtab".to_string())
+                }
+                aux = aux.wrapping_add(vernaux.vna_next as usize);
+            }
+            need = need.wrapping_add(verneed.vn_next as usize);
+        }
+        let mut def = self.verdef;
+        for _ in 0..self.verdefnum {
+            if !mapped(def, ::std::mem::size_of::<Verdef>()) {
+                return Err ("DT_VERDEF entry is not within a loaded segment".to_string())
+            }
+            let verdef = &*(def as *const Verdef);
+            // `matches` only ever reads the first auxiliary entry, i.e., the version's own name
+            let aux = def.wrapping_add(verdef.vd_aux as usize);
+            if !mapped(aux, ::std::mem::size_of::<Verdaux>()) {
+                return Err ("DT_VERDEF auxiliary entry is not within a loaded segment".to_string())
+            }
+            if (*(aux as *const Verdaux)).vda_name as usize >= strsz {
+                return Err ("DT_VERDEF version name is past the end of the strtab".to_string())
+            }
+            def = def.wrapping_add(verdef.vd_next as usize);
+        }
+        Ok (())
+    }
+
+    #[inline(always)]
+    unsafe fn versym (&self, sym_idx: usize) -> u16 {
+        *(self.versym as *const u16).offset(sym_idx as isize)
+    }
+
+    /// The version the (undefined) symbol at `sym_idx` requires, if any
+    pub unsafe fn requested<'a> (&self, strtab: &'a Strtab, sym_idx: usize) -> Option<Version<'a>> {
+        if self.versym == 0 || self.verneed == 0 {
+            return None
+        }
+        let ndx = self.versym(sym_idx) & VERSYM_VERSION;
+        if ndx <= VER_NDX_GLOBAL {
+            return None
+        }
+        let mut need = self.verneed as *const Verneed;
+        for _ in 0..self.verneednum {
+            let mut aux = (need as usize + (*need).vn_aux as usize) as *const Vernaux;
+            for _ in 0..(*need).vn_cnt {
+                if (*aux).vna_other == ndx {
+                    return Some (Version { name: &strtab[(*aux).vna_name as usize], hash: (*aux).vna_hash })
+                }
+                aux = (aux as usize + (*aux).vna_next as usize) as *const Vernaux;
+            }
+            need = (need as usize + (*need).vn_next as usize) as *const Verneed;
+        }
+        None
+    }
+
+    /// Whether the symbol at `sym_idx` in the providing object satisfies `version`; an unversioned reference binds to the default (non-hidden) version only
+    pub unsafe fn matches (&self, strtab: &Strtab, sym_idx: usize, version: Option<&Version>) -> bool {
+        if self.versym == 0 {
+            return true
+        }
+        let versym = self.versym(sym_idx);
+        let ndx = versym & VERSYM_VERSION;
+        match version {
+            None => versym & VERSYM_HIDDEN == 0,
+            Some (_) if ndx <= VER_NDX_GLOBAL => true,
+            Some (version) => {
+                let mut def = self.verdef as *const Verdef;
+                if def.is_null() {
+                    return true
+                }
+                for _ in 0..self.verdefnum {
+                    if (*def).vd_ndx == ndx {
+                        let aux = (def as usize + (*def).vd_aux as usize) as *const Verdaux;
+                        return (*def).vd_hash == version.hash && &strtab[(*aux).vda_name as usize] == version.name
+                    }
+                    def = (def as usize + (*def).vd_next as usize) as *const Verdef;
+                }
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,14 @@
+#include <stdio.h>
+#include <sys/auxv.h>
+
+// prints its arguments and AT_EXECFN, one per line, so `make check` can tell whether running it as `./dryad.so.1 test/args ...`
+// gives it the same view of the world as running it directly
+
+int main (int argc, char** argv){
+  printf("argc: %d\n", argc);
+  for (int i = 0; i < argc; i++){
+    printf("argv[%d]: %s\n", i, argv[i]);
+  }
+  printf("AT_EXECFN: %s\n", (const char*) getauxval(AT_EXECFN));
+  return 0;
+}
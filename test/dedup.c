@@ -0,0 +1,94 @@
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <stdint.h>
+#include <string.h>
+#include <link.h>
+#include <unistd.h>
+#include <limits.h>
+#include <stdlib.h>
+
+// needs test/libdedupa.so by name (found with LD_LIBRARY_PATH=test), and by a relative path through test/libdedupb.so, then dlopens it through a symlink;
+// every one of them is the same file, so there should only ever be one copy of it in the link map.
+// build the libraries first (see the Makefile); run from the repository root with LD_LIBRARY_PATH=test
+
+#define DLEXT_RESERVED_ADDRESS 0x1
+#define LINK "/tmp/libdedupa-link.so"
+
+typedef struct {
+  uint64_t flags;
+  void* reserved_addr;
+  size_t reserved_size;
+  int relro_fd;
+  int library_fd;
+  int64_t library_fd_offset;
+  void* library_namespace;
+} dryad_dlextinfo;
+
+// weak, so this still links (and just skips) without dryad
+extern void* dryad_dlopen_ext (const char* filename, int flags, const dryad_dlextinfo* extinfo) __attribute__((weak));
+extern void* dryad_dlsym (void* handle, const char* symbol) __attribute__((weak));
+extern const char* dryad_dlerror () __attribute__((weak));
+extern int dryad_dl_iterate_phdr (int (*callback)(struct dl_phdr_info*, size_t, void*), void* data) __attribute__((weak));
+
+extern int* dedupa_counter ();
+extern int* dedupb_counter ();
+
+static int count_copies (struct dl_phdr_info* info, size_t size, void* data) {
+  if (strstr(info->dlpi_name, "libdedupa")) ++*(int*)data;
+  return 0;
+}
+
+static int copies () {
+  int n = 0;
+  dryad_dl_iterate_phdr(count_copies, &n);
+  return n;
+}
+
+int main () {
+  if (!dryad_dlopen_ext) {
+    printf("dedup: not running under dryad, skipping\n");
+    return 0;
+  }
+  int failed = 0;
+
+  // 1. by name and by relative path
+  if (copies() != 1 || dedupa_counter() != dedupb_counter()) {
+    printf("dedup: FAIL %d copies of libdedupa.so after loading it by name and by relative path\n", copies());
+    failed = 1;
+  } else {
+    printf("dedup: ok by name and by relative path\n");
+  }
+
+  // 2. through a symlink
+  char path[PATH_MAX];
+  if (!realpath("test/libdedupa.so", path)) {
+    printf("dedup: cannot find test/libdedupa.so; run from the repository root\n");
+    return 2;
+  }
+  unlink(LINK);
+  symlink(path, LINK);
+  void* handle = dryad_dlopen_ext(LINK, RTLD_NOW, NULL);
+  int* (*counter)() = handle ? dryad_dlsym(handle, "dedupa_counter") : NULL;
+  if (!counter || counter() != dedupa_counter() || copies() != 1) {
+    printf("dedup: FAIL %d copies of libdedupa.so after dlopening it through a symlink: %s\n", copies(), handle ? "different object" : dryad_dlerror());
+    failed = 1;
+  } else {
+    printf("dedup: ok through a symlink\n");
+  }
+
+  // 3. it can't be put anywhere else now, so asking to is an error, not a silently ignored flag
+  static char reserved[4096] __attribute__((aligned(4096)));
+  dryad_dlextinfo info = { 0 };
+  info.flags = DLEXT_RESERVED_ADDRESS;
+  info.reserved_addr = reserved;
+  info.reserved_size = sizeof(reserved);
+  if (dryad_dlopen_ext(LINK, RTLD_NOW, &info)) {
+    printf("dedup: FAIL dlopen_ext with a reserved address returned the already loaded object\n");
+    failed = 1;
+  } else {
+    printf("dedup: ok reserved address for an already loaded object: %s\n", dryad_dlerror());
+  }
+
+  unlink(LINK);
+  return failed;
+}
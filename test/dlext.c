@@ -0,0 +1,180 @@
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <stdint.h>
+#include <string.h>
+#include <fcntl.h>
+#include <unistd.h>
+#include <sys/mman.h>
+#include <sys/wait.h>
+#include <link.h>
+
+// exercises dryad_dlopen_ext with plain files: loading into a reserved range, from an fd at an offset, and writing then sharing a relro file;
+// the relro is written and shared by two forked children, since the same file can only be loaded once per process
+// build test/libdlext.so first (see the Makefile); run from the repository root
+
+#define DLEXT_RESERVED_ADDRESS      0x1
+#define DLEXT_WRITE_RELRO           0x4
+#define DLEXT_USE_RELRO             0x8
+#define DLEXT_USE_LIBRARY_FD        0x10
+#define DLEXT_USE_LIBRARY_FD_OFFSET 0x20
+
+typedef struct {
+  uint64_t flags;
+  void* reserved_addr;
+  size_t reserved_size;
+  int relro_fd;
+  int library_fd;
+  int64_t library_fd_offset;
+  void* library_namespace;
+} dryad_dlextinfo;
+
+// weak, so this still links (and just skips) without dryad
+extern void* dryad_dlopen_ext (const char* filename, int flags, const dryad_dlextinfo* extinfo) __attribute__((weak));
+extern void* dryad_dlsym (void* handle, const char* symbol) __attribute__((weak));
+extern const char* dryad_dlerror () __attribute__((weak));
+extern int dryad_dl_iterate_phdr (int (*callback)(struct dl_phdr_info*, size_t, void*), void* data) __attribute__((weak));
+
+#define LIB "test/libdlext.so"
+#define RELRO "/tmp/dlext.relro"
+#define PADDED "/tmp/dlext.padded"
+#define RESERVED_SIZE (1 << 20)
+
+static int check (void* handle, const char* what) {
+  if (!handle) {
+    printf("dlext: FAIL %s: %s\n", what, dryad_dlerror());
+    return 1;
+  }
+  int (*answer)() = dryad_dlsym(handle, "dlext_answer");
+  const char* const* (*words)() = dryad_dlsym(handle, "dlext_words");
+  if (!answer || !words || answer() != 42 || strcmp(words()[1], "relro") != 0) {
+    printf("dlext: FAIL %s: library doesn't work\n", what);
+    return 1;
+  }
+  printf("dlext: ok %s\n", what);
+  return 0;
+}
+
+struct relro { const char* name; uintptr_t start, end; };
+
+// where `name`'s PT_GNU_RELRO is, rounded to pages the way the dynamic linker protects (and shares) it
+static int find_relro (struct dl_phdr_info* info, size_t size, void* data) {
+  struct relro* relro = data;
+  if (strcmp(info->dlpi_name, relro->name) != 0) return 0;
+  for (int i = 0; i < info->dlpi_phnum; i++) {
+    if (info->dlpi_phdr[i].p_type == PT_GNU_RELRO) {
+      long page = sysconf(_SC_PAGESIZE);
+      relro->start = (info->dlpi_addr + info->dlpi_phdr[i].p_vaddr) & ~(page - 1);
+      relro->end = (info->dlpi_addr + info->dlpi_phdr[i].p_vaddr + info->dlpi_phdr[i].p_memsz) & ~(page - 1);
+    }
+  }
+  return 1;
+}
+
+// whether all of [start, end) is mapped from `path`
+static int backed_by (uintptr_t start, uintptr_t end, const char* path) {
+  char line[512];
+  uintptr_t covered = start;
+  FILE* maps = fopen("/proc/self/maps", "r");
+  while (maps && fgets(line, sizeof(line), maps)) {
+    uintptr_t from, to;
+    if (sscanf(line, "%lx-%lx", &from, &to) == 2 && strstr(line, path) && from <= covered && to > covered) covered = to;
+  }
+  if (maps) fclose(maps);
+  return covered >= end;
+}
+
+// runs `test` in a child, so each load starts from a process which has never seen the library, and gets the reserved range to itself
+static int in_child (int (*test)()) {
+  fflush(stdout);
+  pid_t pid = fork();
+  if (pid == 0) {
+    int failed = test();
+    fflush(stdout);
+    _exit(failed);
+  }
+  int status;
+  return waitpid(pid, &status, 0) != pid || !WIFEXITED(status) || WEXITSTATUS(status) != 0;
+}
+
+static void* reserved;
+static int lib;
+static int relro;
+
+// 1. into a reserved range, from an fd, writing the relro out
+static int write_relro () {
+  dryad_dlextinfo info = { 0 };
+  info.flags = DLEXT_RESERVED_ADDRESS | DLEXT_USE_LIBRARY_FD | DLEXT_WRITE_RELRO;
+  info.reserved_addr = reserved;
+  info.reserved_size = RESERVED_SIZE;
+  info.library_fd = lib;
+  info.relro_fd = relro;
+  void* handle = dryad_dlopen_ext("libdlext-1", RTLD_NOW, &info);
+  int failed = check(handle, "reserved address, library fd, write relro");
+  if (handle) {
+    uintptr_t answer = (uintptr_t)dryad_dlsym(handle, "dlext_answer");
+    if (answer < (uintptr_t)reserved || answer >= (uintptr_t)reserved + RESERVED_SIZE) {
+      printf("dlext: FAIL library at %p is outside the reserved range %p\n", (void*)answer, reserved);
+      failed = 1;
+    }
+  }
+  return failed;
+}
+
+// 2. in another process, at the same address, so the relocated relro comes out the same and every page of it can be shared from the file
+static int use_relro () {
+  lseek(relro, 0, SEEK_SET);
+  dryad_dlextinfo info = { 0 };
+  info.flags = DLEXT_RESERVED_ADDRESS | DLEXT_USE_LIBRARY_FD | DLEXT_USE_RELRO;
+  info.reserved_addr = reserved;
+  info.reserved_size = RESERVED_SIZE;
+  info.library_fd = lib;
+  info.relro_fd = relro;
+  void* handle = dryad_dlopen_ext("libdlext-2", RTLD_NOW, &info);
+  int failed = check(handle, "use relro");
+  struct relro range = { "libdlext-2", 0, 0 };
+  dryad_dl_iterate_phdr(find_relro, &range);
+  if (range.start >= range.end) {
+    printf("dlext: FAIL libdlext-2 has no relro to share\n");
+    failed = 1;
+  } else if (!backed_by(range.start, range.end, RELRO)) {
+    printf("dlext: FAIL relro at %p-%p isn't mapped from %s\n", (void*)range.start, (void*)range.end, RELRO);
+    failed = 1;
+  } else {
+    printf("dlext: ok relro at %p-%p is mapped from %s\n", (void*)range.start, (void*)range.end, RELRO);
+  }
+  return failed;
+}
+
+int main () {
+  if (!dryad_dlopen_ext) {
+    printf("dlext: not running under dryad, skipping\n");
+    return 0;
+  }
+  int failed = 0;
+
+  // reserved before forking, so both children have it at the same address
+  reserved = mmap(NULL, RESERVED_SIZE, PROT_NONE, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+  lib = open(LIB, O_RDONLY);
+  relro = open(RELRO, O_RDWR | O_CREAT | O_TRUNC, 0644);
+  failed |= in_child(write_relro);
+  failed |= in_child(use_relro);
+
+  // 3. from a page into a bigger file, like an uncompressed library in an apk
+  int padded = open(PADDED, O_RDWR | O_CREAT | O_TRUNC, 0644);
+  char buf[4096];
+  memset(buf, 0, sizeof(buf));
+  write(padded, buf, sizeof(buf));
+  lseek(lib, 0, SEEK_SET);
+  ssize_t n;
+  while ((n = read(lib, buf, sizeof(buf))) > 0) write(padded, buf, n);
+  dryad_dlextinfo info = { 0 };
+  info.flags = DLEXT_USE_LIBRARY_FD | DLEXT_USE_LIBRARY_FD_OFFSET;
+  info.library_fd = padded;
+  info.library_fd_offset = 4096;
+  failed |= check(dryad_dlopen_ext("libdlext-3", RTLD_NOW, &info), "library fd offset");
+
+  close(lib);
+  close(relro);
+  close(padded);
+  return failed;
+}
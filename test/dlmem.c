@@ -0,0 +1,107 @@
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <fcntl.h>
+#include <unistd.h>
+#include <link.h>
+#include <sys/stat.h>
+#include <sys/syscall.h>
+
+// exercises dryad_dlopen_mem and dryad_dlopen_fd at a non-zero offset into a memfd, with test/libdlext.so, neither of which ever touches a path;
+// and that a dlopen which fails part way (test/libdlundef.so, linked lazily, but opened with RTLD_NOW) leaves nothing of itself behind.
+// build the libraries first (see the Makefile); run from the repository root
+
+extern void* dryad_dlopen_fd (int fd, off_t offset, int flags) __attribute__((weak));
+extern void* dryad_dlopen_mem (const void* ptr, size_t len, const char* name, int flags) __attribute__((weak));
+extern void* dryad_dlsym (void* handle, const char* symbol) __attribute__((weak));
+extern const char* dryad_dlerror () __attribute__((weak));
+extern int dryad_dl_iterate_phdr (int (*callback)(struct dl_phdr_info*, size_t, void*), void* data) __attribute__((weak));
+
+static char* slurp (const char* path, size_t* len) {
+  int fd = open(path, O_RDONLY);
+  struct stat st;
+  if (fd < 0 || fstat(fd, &st) < 0) return NULL;
+  char* bytes = malloc(st.st_size);
+  *len = read(fd, bytes, st.st_size);
+  close(fd);
+  return bytes;
+}
+
+static int check (void* handle, const char* what) {
+  if (!handle) {
+    printf("dlmem: FAIL %s: %s\n", what, dryad_dlerror());
+    return 1;
+  }
+  int (*answer)() = dryad_dlsym(handle, "dlext_answer");
+  if (!answer || answer() != 42) {
+    printf("dlmem: FAIL %s: library doesn't work\n", what);
+    return 1;
+  }
+  printf("dlmem: ok %s\n", what);
+  return 0;
+}
+
+static int count (struct dl_phdr_info* info, size_t size, void* data) {
+  ++*(int*)data;
+  return 0;
+}
+
+static int objects () {
+  int n = 0;
+  dryad_dl_iterate_phdr(count, &n);
+  return n;
+}
+
+int main () {
+  if (!dryad_dlopen_mem) {
+    printf("dlmem: not running under dryad, skipping\n");
+    return 0;
+  }
+  int failed = 0;
+  size_t len;
+  char* lib = slurp("test/libdlext.so", &len);
+  if (!lib) {
+    printf("dlmem: cannot read test/libdlext.so; run from the repository root\n");
+    return 2;
+  }
+
+  // 1. from a buffer, which we free straight away, since it's copied
+  void* handle = dryad_dlopen_mem(lib, len, "libdlext-mem", RTLD_NOW);
+  failed |= check(handle, "from memory");
+
+  // 2. a page into a memfd
+  int fd = syscall(SYS_memfd_create, "dlmem", 0);
+  char page[4096];
+  memset(page, 0, sizeof(page));
+  write(fd, page, sizeof(page));
+  write(fd, lib, len);
+  free(lib);
+  failed |= check(dryad_dlopen_fd(fd, 4096, RTLD_NOW), "memfd at an offset");
+  close(fd);
+
+  // 3. failing part way, after it's mapped, doesn't leave it in the link map, and trying again fails the same way rather than finding a half loaded object
+  lib = slurp("test/libdlundef.so", &len);
+  int before = objects();
+  for (int i = 0; i < 2; i++) {
+    if (dryad_dlopen_mem(lib, len, "libdlundef", RTLD_NOW)) {
+      printf("dlmem: FAIL libdlundef.so loaded, but nothing defines dlundef_missing\n");
+      failed = 1;
+    } else if (objects() != before) {
+      printf("dlmem: FAIL %d objects in the link map after a failed dlopen, not %d\n", objects(), before);
+      failed = 1;
+    } else {
+      printf("dlmem: ok failed dlopen left nothing behind: %s\n", dryad_dlerror());
+    }
+  }
+  free(lib);
+
+  // 4. a null symbol name is an error, not a crash
+  if (dryad_dlsym(handle, NULL) || !dryad_dlerror()) {
+    printf("dlmem: FAIL dryad_dlsym with a null name didn't fail\n");
+    failed = 1;
+  } else {
+    printf("dlmem: ok dryad_dlsym with a null name fails\n");
+  }
+  return failed;
+}
@@ -0,0 +1,18 @@
+#include <stdio.h>
+#include <stdlib.h>
+
+// exits with libfini.so's destructor still to run; it prints "fini: ok ..." as the very last line if the dynamic linker runs destructors at all, and in the right order.
+// build test/libfini.so first (see the Makefile); run from the repository root with LD_LIBRARY_PATH=test
+
+extern int fini_atexit_ran;
+
+static void at_exit () {
+  printf("fini: program atexit handler\n");
+  fini_atexit_ran = 1;
+}
+
+int main () {
+  atexit(at_exit);
+  printf("fini: main returning\n");
+  return 0;
+}
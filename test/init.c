@@ -0,0 +1,35 @@
+#include <stdio.h>
+#include <string.h>
+
+// checks constructor order: our preinit_array first, then libinitb.so, then libinita.so which needs it, then our own constructor (from crt1).
+// build test/libinitb.so and test/libinita.so first (see the Makefile); run from the repository root with LD_LIBRARY_PATH=test
+
+extern char init_log[];
+extern int init_len;
+extern void init_note (char c);
+
+static int preinit_saw = -1;
+
+static void preinit (int argc, char** argv, char** envp) {
+  preinit_saw = init_len;
+  init_note('p');
+}
+
+__attribute__((section(".preinit_array"), used)) static void (*preinit_entry)(int, char**, char**) = preinit;
+
+__attribute__((constructor)) static void init_exe () {
+  init_note('e');
+}
+
+int main () {
+  if (preinit_saw != 0) {
+    printf("init: FAIL preinit_array ran after %d constructors\n", preinit_saw);
+    return 1;
+  }
+  if (strcmp(init_log, "pbae") != 0) {
+    printf("init: FAIL constructors ran in the order %s, expected pbae\n", init_log);
+    return 1;
+  }
+  printf("init: ok %s\n", init_log);
+  return 0;
+}
@@ -0,0 +1,8 @@
+// loaded by test/dedup under three names: libdedupa.so from the library path, test/libdedupa.so (test/libdedupb.so's DT_NEEDED, relative to the cwd),
+// and a symlink; built without a soname, so the names are all there is to go on, yet they're all the same file, and so should all be the one object
+
+static int counter;
+
+int* dedupa_counter () {
+  return &counter;
+}
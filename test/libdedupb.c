@@ -0,0 +1,7 @@
+// linked against test/libdedupa.so by path, so its DT_NEEDED is the relative path "test/libdedupa.so"
+
+extern int* dedupa_counter ();
+
+int* dedupb_counter () {
+  return dedupa_counter();
+}
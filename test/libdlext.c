@@ -0,0 +1,10 @@
+// loaded by test/dlext; the table of pointers is relocated at load time, and lives in .data.rel.ro, i.e., the RELRO
+static const char* const words[] = { "shared", "relro", "pages" };
+
+const char* const* dlext_words () {
+  return words;
+}
+
+int dlext_answer () {
+  return 42;
+}
@@ -0,0 +1,7 @@
+// loaded by test/dlmem, which expects loading it with RTLD_NOW to fail, since nothing defines this; it's linked with -z lazy, so that's down to RTLD_NOW alone
+
+extern int dlundef_missing ();
+
+int dlundef_call () {
+  return dlundef_missing();
+}
@@ -0,0 +1,16 @@
+#include <stdio.h>
+#include <unistd.h>
+
+// a destructor that reports it ran, and that the program's atexit handlers had already run by then, like they have under ld.so
+
+int fini_atexit_ran;
+
+__attribute__((destructor)) static void fini () {
+  if (!fini_atexit_ran) {
+    printf("fini: FAIL library destructor ran before the program's atexit handler\n");
+    fflush(stdout);
+    _exit(1);
+  }
+  printf("fini: ok library destructor ran at exit\n");
+  fflush(stdout);
+}
@@ -0,0 +1,7 @@
+// needs libinitb.so, so must be constructed after it, even though it comes first in the executable's DT_NEEDED
+
+extern void init_note (char c);
+
+__attribute__((constructor)) static void inita () {
+  init_note('a');
+}
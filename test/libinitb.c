@@ -0,0 +1,14 @@
+// the bottom of init's dependency chain: everyone who needs us logs into our buffer, so we have to be constructed first
+
+char init_log[16];
+int init_len;
+
+void init_note (char c) {
+  if (init_len < (int)sizeof(init_log) - 1)
+    init_log[init_len++] = c;
+}
+
+// no DT_INIT, only DT_INIT_ARRAY, like everything a modern toolchain builds
+__attribute__((constructor)) static void initb () {
+  init_note('b');
+}
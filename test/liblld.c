@@ -0,0 +1,12 @@
+#include <stdio.h>
+
+// linked with lld, which (unlike binutils) puts .gnu.version, .gnu.version_r and .gnu.hash between .dynsym and .dynstr;
+// calling into libc makes sure there's a .gnu.version_r to get in the way
+
+int lld_answer () {
+  return 42;
+}
+
+void lld_hello () {
+  printf("lld: hello from an lld linked library\n");
+}
@@ -0,0 +1,6 @@
+// test/missing needs this, but make check deletes it before running it, so ldd has something to say "not found" about
+int missing_data = 1;
+
+int missing (void){
+  return 0;
+}
@@ -0,0 +1,13 @@
+#include <stdio.h>
+
+// linked with --hash-style=sysv, so it has a DT_HASH and no DT_GNU_HASH; the data symbol is there to need a GLOB_DAT in test/sysv as well as a JUMP_SLOT
+
+int sysv_data = 7;
+
+int sysv_answer () {
+  return 42;
+}
+
+void sysv_hello () {
+  printf("sysv: hello from a library with only a sysv hash table\n");
+}
@@ -0,0 +1,12 @@
+// registers thread_local destructors the way libstdc++ and rust's std do: with __cxa_thread_atexit_impl, against this library's __dso_handle,
+// so whoever implements it knows which object has to stay mapped until they've run
+
+extern void* __dso_handle;
+extern int __cxa_thread_atexit_impl (void (*func) (void*), void* obj, void* dso_symbol) __attribute__((weak));
+
+int tlsdtor_register (void (*func) (void*), void* obj) {
+  if (!__cxa_thread_atexit_impl) {
+    return -1;
+  }
+  return __cxa_thread_atexit_impl(func, obj, &__dso_handle);
+}
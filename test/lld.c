@@ -0,0 +1,18 @@
+#include <stdio.h>
+
+// links against test/liblld.so, built with -fuse-ld=lld; a dynamic linker which sizes the symtab by assuming the strtab follows it
+// mistakes lld's version and hash tables for symbols, and refuses to load the library at all.
+// build test/liblld.so first (see the Makefile); run from the repository root with LD_LIBRARY_PATH=test
+
+extern int lld_answer ();
+extern void lld_hello ();
+
+int main () {
+  lld_hello();
+  if (lld_answer() != 42) {
+    printf("lld: FAIL lld_answer returned %d\n", lld_answer());
+    return 1;
+  }
+  printf("lld: ok\n");
+  return 0;
+}
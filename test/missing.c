@@ -0,0 +1,8 @@
+// linked against test/libmissing.so, which make check deletes before it runs this as ldd would, to check what we report:
+// `libmissing.so => not found`, and with LD_WARN (ldd -d and -r), the symbols nothing defines
+extern int missing_data;
+extern int missing (void);
+
+int main (){
+  return missing() + missing_data;
+}
@@ -0,0 +1,33 @@
+#include <stdio.h>
+#include <stdint.h>
+#include <string.h>
+
+// build with -Wl,-z,relro,-z,now: the whole GOT, .got.plt included, is then inside PT_GNU_RELRO,
+// and the dynamic linker must have made it read-only by the time main runs
+extern char _GLOBAL_OFFSET_TABLE_[];
+
+int main () {
+  uintptr_t got = (uintptr_t)_GLOBAL_OFFSET_TABLE_;
+  FILE* maps = fopen("/proc/self/maps", "r");
+  char line[512];
+  if (!maps) {
+    printf("relro: cannot open /proc/self/maps\n");
+    return 2;
+  }
+  while (fgets(line, sizeof(line), maps)) {
+    uintptr_t start, end;
+    char perms[5];
+    if (sscanf(line, "%lx-%lx %4s", &start, &end, perms) == 3 && got >= start && got < end) {
+      fclose(maps);
+      if (perms[1] == 'w') {
+        printf("relro: FAIL GOT at %p is writable: %s", (void*)got, line);
+        return 1;
+      }
+      printf("relro: ok GOT at %p is %s\n", (void*)got, perms);
+      return 0;
+    }
+  }
+  fclose(maps);
+  printf("relro: GOT at %p not mapped?\n", (void*)got);
+  return 2;
+}
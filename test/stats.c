@@ -0,0 +1,74 @@
+#include <stdio.h>
+#include <stdint.h>
+#include <stdlib.h>
+#include <math.h>
+
+// prints what dryad_get_stats says starting this program cost; it exits non-zero if the numbers don't add up.
+// LD_DEBUG=statistics prints much the same thing before main runs
+
+typedef struct {
+  uint64_t self_relocation;
+  uint64_t tls;
+  uint64_t loading;
+  uint64_t relocate_got;
+  uint64_t relocate_plt;
+  uint64_t ifuncs;
+  uint64_t constructors;
+  uint64_t total;
+} dryad_timings;
+
+typedef struct {
+  size_t relative;
+  size_t symbolic;
+  size_t lookups;
+  size_t ifuncs;
+  uint64_t ifunc_time;
+} dryad_counts;
+
+typedef struct {
+  dryad_timings timings;
+  dryad_counts totals;
+  size_t lazy_lookups;
+  size_t objects;
+} dryad_stats;
+
+typedef struct {
+  const char* name;
+  dryad_counts counts;
+} dryad_object_stats;
+
+// weak, so this still links (and just skips) without dryad
+extern size_t dryad_get_stats (dryad_stats* stats, dryad_object_stats* objects, size_t count) __attribute__((weak));
+
+int main () {
+  if (!dryad_get_stats) {
+    printf("stats: not running under dryad, skipping\n");
+    return 0;
+  }
+  // a lazy binding or two before we ask
+  volatile double x = 2.0;
+  printf("stats: sqrt(2) = %f\n", sqrt(x));
+
+  dryad_stats stats;
+  size_t count = dryad_get_stats(&stats, NULL, 0);
+  dryad_object_stats* objects = calloc(count, sizeof(dryad_object_stats));
+  dryad_get_stats(&stats, objects, count);
+
+  dryad_timings* t = &stats.timings;
+  printf("stats: total %lu ns: self relocation %lu, tls %lu, loading %lu, got %lu, plt %lu, ifuncs %lu, constructors %lu\n",
+         t->total, t->self_relocation, t->tls, t->loading, t->relocate_got, t->relocate_plt, t->ifuncs, t->constructors);
+  printf("stats: %lu relative, %lu symbolic, %lu lookups, %lu lazy lookups, %lu ifuncs in %lu objects\n",
+         stats.totals.relative, stats.totals.symbolic, stats.totals.lookups, stats.lazy_lookups, stats.totals.ifuncs, stats.objects);
+
+  int failed = count == 0 || stats.objects != count || stats.lazy_lookups == 0;
+  size_t relative = 0;
+  for (size_t i = 0; i < count; i++) {
+    printf("stats:   %s: %lu relative, %lu symbolic, %lu lookups\n", objects[i].name, objects[i].counts.relative, objects[i].counts.symbolic, objects[i].counts.lookups);
+    relative += objects[i].counts.relative;
+  }
+  failed |= relative != stats.totals.relative;
+  failed |= t->self_relocation + t->loading + t->relocate_got + t->relocate_plt + t->ifuncs + t->constructors > t->total;
+  printf("stats: %s\n", failed ? "FAIL" : "ok");
+  free(objects);
+  return failed;
+}
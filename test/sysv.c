@@ -0,0 +1,18 @@
+#include <stdio.h>
+
+// links against test/libsysv.so, which only has a sysv hash table, so none of its symbols can be found by walking a gnu one.
+// build test/libsysv.so first (see the Makefile); run from the repository root with LD_LIBRARY_PATH=test
+
+extern int sysv_data;
+extern int sysv_answer ();
+extern void sysv_hello ();
+
+int main () {
+  sysv_hello();
+  if (sysv_answer() != 42 || sysv_data != 7) {
+    printf("sysv: FAIL sysv_answer returned %d and sysv_data is %d\n", sysv_answer(), sysv_data);
+    return 1;
+  }
+  printf("sysv: ok\n");
+  return 0;
+}
@@ -0,0 +1,43 @@
+#include <stdio.h>
+#include <pthread.h>
+
+// a thread's thread_local destructor (registered through test/libtlsdtor.so) has to run when the thread exits, and the main thread's at exit;
+// its last line of output is "tlsdtor: ok ..." from the latter.
+// build test/libtlsdtor.so first (see the Makefile); run from the repository root with LD_LIBRARY_PATH=test
+
+extern int tlsdtor_register (void (*func) (void*), void* obj);
+
+static void set (void* flag) {
+  *(int*) flag = 1;
+}
+
+static void report (void* unused) {
+  printf("tlsdtor: ok main thread destructor ran at exit\n");
+  fflush(stdout);
+}
+
+static void* thread (void* flag) {
+  if (tlsdtor_register(set, flag) != 0) {
+    return (void*) 1;
+  }
+  return NULL;
+}
+
+int main () {
+  int ran = 0;
+  void* res;
+  pthread_t t;
+  pthread_create(&t, NULL, thread, &ran);
+  pthread_join(t, &res);
+  if (res != NULL) {
+    printf("tlsdtor: no __cxa_thread_atexit_impl, skipping\n");
+    return 0;
+  }
+  if (!ran) {
+    printf("tlsdtor: FAIL thread's destructor didn't run when it exited\n");
+    return 1;
+  }
+  printf("tlsdtor: thread's destructor ran when it exited\n");
+  tlsdtor_register(report, NULL);
+  return 0;
+}